@@ -0,0 +1,14 @@
+ALTER TABLE prompts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE TABLE prompt_versions (
+    prompt_id UUID NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (prompt_id, version)
+);
+
+-- Seed history for prompts created before versioning existed
+INSERT INTO prompt_versions (prompt_id, version, title, content, created_at)
+SELECT id, version, title, content, COALESCE(created_at, NOW()) FROM prompts;
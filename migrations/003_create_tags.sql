@@ -0,0 +1,13 @@
+CREATE TABLE tags (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE prompt_tags (
+    prompt_id UUID NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    tag_id UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (prompt_id, tag_id)
+);
+
+CREATE INDEX prompt_tags_tag_id_idx ON prompt_tags (tag_id);
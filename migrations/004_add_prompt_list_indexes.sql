@@ -0,0 +1,6 @@
+-- Keyset pagination relies on created_at always being present
+UPDATE prompts SET created_at = NOW() WHERE created_at IS NULL;
+ALTER TABLE prompts ALTER COLUMN created_at SET NOT NULL;
+
+CREATE INDEX prompts_created_at_id_idx ON prompts (created_at, id);
+CREATE INDEX prompts_title_id_idx ON prompts (title, id);
@@ -0,0 +1,7 @@
+ALTER TABLE prompts ADD COLUMN search_vector tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', title), 'A') ||
+        setweight(to_tsvector('english', content), 'B')
+    ) STORED;
+
+CREATE INDEX prompts_search_vector_idx ON prompts USING GIN (search_vector);
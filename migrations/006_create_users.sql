@@ -0,0 +1,10 @@
+CREATE TABLE users (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Prompts created before accounts existed have no owner
+ALTER TABLE prompts ADD COLUMN owner_id UUID REFERENCES users(id);
+CREATE INDEX prompts_owner_id_idx ON prompts (owner_id);
@@ -0,0 +1,37 @@
+CREATE TABLE workspaces (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE workspace_members (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE INDEX workspace_members_user_id_idx ON workspace_members (user_id);
+
+ALTER TABLE prompts ADD COLUMN workspace_id UUID REFERENCES workspaces(id) ON DELETE CASCADE;
+
+-- Existing prompts and users move into a shared default workspace, owned by the first user
+WITH ws AS (
+    INSERT INTO workspaces (name)
+    SELECT 'Default' WHERE EXISTS (SELECT 1 FROM prompts) OR EXISTS (SELECT 1 FROM users)
+    RETURNING id
+), members AS (
+    INSERT INTO workspace_members (workspace_id, user_id, role)
+    SELECT ws.id, u.id,
+        CASE WHEN u.id = (SELECT id FROM users ORDER BY created_at LIMIT 1) THEN 'owner' ELSE 'editor' END
+    FROM ws, users u
+)
+UPDATE prompts SET workspace_id = ws.id FROM ws;
+
+ALTER TABLE prompts ALTER COLUMN workspace_id SET NOT NULL;
+
+DROP INDEX prompts_created_at_id_idx;
+DROP INDEX prompts_title_id_idx;
+CREATE INDEX prompts_workspace_created_at_id_idx ON prompts (workspace_id, created_at, id);
+CREATE INDEX prompts_workspace_title_id_idx ON prompts (workspace_id, title, id);
@@ -0,0 +1,15 @@
+-- `revision` changes on every write and backs the prompt's ETag
+ALTER TABLE prompts
+    ADD COLUMN revision BIGINT NOT NULL DEFAULT 1,
+    ADD COLUMN updated_at TIMESTAMPTZ;
+
+UPDATE prompts p SET
+    revision = p.version,
+    updated_at = COALESCE(
+        (SELECT MAX(v.created_at) FROM prompt_versions v WHERE v.prompt_id = p.id),
+        p.created_at
+    );
+
+ALTER TABLE prompts
+    ALTER COLUMN updated_at SET NOT NULL,
+    ALTER COLUMN updated_at SET DEFAULT NOW();
@@ -0,0 +1,12 @@
+-- Deleted prompts are kept as tombstones until the purge task removes them
+ALTER TABLE prompts ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- Listing only ever looks at live prompts
+DROP INDEX prompts_workspace_created_at_id_idx;
+DROP INDEX prompts_workspace_title_id_idx;
+CREATE INDEX prompts_workspace_created_at_id_idx ON prompts (workspace_id, created_at, id)
+    WHERE deleted_at IS NULL;
+CREATE INDEX prompts_workspace_title_id_idx ON prompts (workspace_id, title, id)
+    WHERE deleted_at IS NULL;
+
+CREATE INDEX prompts_deleted_at_idx ON prompts (deleted_at) WHERE deleted_at IS NOT NULL;
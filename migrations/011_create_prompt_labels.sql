@@ -0,0 +1,23 @@
+-- A label such as `production` names one version of a prompt
+CREATE TABLE prompt_labels (
+    prompt_id UUID NOT NULL,
+    name TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (prompt_id, name),
+    FOREIGN KEY (prompt_id, version) REFERENCES prompt_versions (prompt_id, version) ON DELETE CASCADE
+);
+
+-- Every move of every label, so promotions can be audited
+CREATE TABLE prompt_label_moves (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    prompt_id UUID NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    from_version INTEGER,
+    to_version INTEGER NOT NULL,
+    moved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    moved_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX prompt_label_moves_prompt_id_name_idx ON prompt_label_moves (prompt_id, name, id);
@@ -0,0 +1,34 @@
+-- A readable handle for prompts, unique within a workspace. Trashed prompts
+-- keep theirs so restoring never collides.
+ALTER TABLE prompts ADD COLUMN slug TEXT;
+
+-- Existing prompts get a slug from their title; repeats are told apart by id
+UPDATE prompts p SET slug = CASE
+        WHEN s.n = 1 THEN s.base
+        ELSE s.base || '-' || left(replace(p.id::text, '-', ''), 8)
+    END
+FROM (
+    SELECT id, base, row_number() OVER (PARTITION BY workspace_id, base ORDER BY created_at, id) AS n
+    FROM (
+        SELECT id, workspace_id, created_at, COALESCE(
+            NULLIF(btrim(left(regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g'), 56), '-'), ''),
+            'prompt'
+        ) AS base
+        FROM prompts
+    ) b
+) s
+WHERE s.id = p.id;
+
+ALTER TABLE prompts ALTER COLUMN slug SET NOT NULL;
+CREATE UNIQUE INDEX prompts_workspace_slug_idx ON prompts (workspace_id, slug);
+
+-- Slugs prompts used to have, so old references keep resolving
+CREATE TABLE prompt_slug_redirects (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    slug TEXT NOT NULL,
+    prompt_id UUID NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, slug)
+);
+
+CREATE INDEX prompt_slug_redirects_prompt_id_idx ON prompt_slug_redirects (prompt_id);
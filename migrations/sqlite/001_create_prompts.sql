@@ -0,0 +1,8 @@
+-- UUIDs are stored as 16-byte blobs and timestamps as fixed-width RFC 3339
+-- text, both generated by the application, so they sort like Postgres does
+CREATE TABLE prompts (
+    id BLOB PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
@@ -0,0 +1,10 @@
+ALTER TABLE prompts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE TABLE prompt_versions (
+    prompt_id BLOB NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (prompt_id, version)
+);
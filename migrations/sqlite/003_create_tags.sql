@@ -0,0 +1,13 @@
+CREATE TABLE tags (
+    id BLOB PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE prompt_tags (
+    prompt_id BLOB NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    tag_id BLOB NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (prompt_id, tag_id)
+);
+
+CREATE INDEX prompt_tags_tag_id_idx ON prompt_tags (tag_id);
@@ -0,0 +1,2 @@
+CREATE INDEX prompts_created_at_id_idx ON prompts (created_at, id);
+CREATE INDEX prompts_title_id_idx ON prompts (title, id);
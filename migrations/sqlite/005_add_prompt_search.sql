@@ -0,0 +1,44 @@
+-- External-content FTS5 index over prompts, kept in sync by triggers. It's
+-- keyed by a search key of its own rather than the implicit rowid of
+-- `prompts`, which VACUUM may renumber because the primary key is a blob.
+CREATE TABLE prompt_search_keys (
+    seq INTEGER PRIMARY KEY,
+    prompt_id BLOB NOT NULL UNIQUE REFERENCES prompts(id) ON DELETE CASCADE
+);
+
+INSERT INTO prompt_search_keys (prompt_id) SELECT id FROM prompts ORDER BY rowid;
+
+-- The indexed text, keyed by search key
+CREATE VIEW prompts_search AS
+    SELECT k.seq, p.title, p.content
+    FROM prompt_search_keys k JOIN prompts p ON p.id = k.prompt_id;
+
+CREATE VIRTUAL TABLE prompts_fts USING fts5(
+    title,
+    content,
+    content = 'prompts_search',
+    content_rowid = 'seq',
+    tokenize = 'porter unicode61'
+);
+
+CREATE TRIGGER prompts_fts_insert AFTER INSERT ON prompts BEGIN
+    INSERT INTO prompt_search_keys (prompt_id) VALUES (new.id);
+    INSERT INTO prompts_fts (rowid, title, content)
+    VALUES ((SELECT seq FROM prompt_search_keys WHERE prompt_id = new.id), new.title, new.content);
+END;
+
+-- Runs before the key row goes with the prompt's cascade
+CREATE TRIGGER prompts_fts_delete BEFORE DELETE ON prompts BEGIN
+    INSERT INTO prompts_fts (prompts_fts, rowid, title, content)
+    VALUES ('delete', (SELECT seq FROM prompt_search_keys WHERE prompt_id = old.id), old.title, old.content);
+END;
+
+CREATE TRIGGER prompts_fts_update AFTER UPDATE OF title, content ON prompts BEGIN
+    INSERT INTO prompts_fts (prompts_fts, rowid, title, content)
+    VALUES ('delete', (SELECT seq FROM prompt_search_keys WHERE prompt_id = old.id), old.title, old.content);
+    INSERT INTO prompts_fts (rowid, title, content)
+    VALUES ((SELECT seq FROM prompt_search_keys WHERE prompt_id = new.id), new.title, new.content);
+END;
+
+-- Indexes prompts created before this migration
+INSERT INTO prompts_fts (prompts_fts) VALUES ('rebuild');
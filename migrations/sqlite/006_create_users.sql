@@ -0,0 +1,9 @@
+CREATE TABLE users (
+    id BLOB PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+ALTER TABLE prompts ADD COLUMN owner_id BLOB REFERENCES users(id);
+CREATE INDEX prompts_owner_id_idx ON prompts (owner_id);
@@ -0,0 +1,24 @@
+CREATE TABLE workspaces (
+    id BLOB PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE workspace_members (
+    workspace_id BLOB NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE INDEX workspace_members_user_id_idx ON workspace_members (user_id);
+
+-- SQLite can't add a NOT NULL column in place; every insert sets it. There is
+-- no pre-workspace SQLite data to move into a default workspace.
+ALTER TABLE prompts ADD COLUMN workspace_id BLOB REFERENCES workspaces(id) ON DELETE CASCADE;
+
+DROP INDEX prompts_created_at_id_idx;
+DROP INDEX prompts_title_id_idx;
+CREATE INDEX prompts_workspace_created_at_id_idx ON prompts (workspace_id, created_at, id);
+CREATE INDEX prompts_workspace_title_id_idx ON prompts (workspace_id, title, id);
@@ -0,0 +1,11 @@
+-- `revision` changes on every write and backs the prompt's ETag. The empty
+-- default only satisfies NOT NULL; every write sets updated_at.
+ALTER TABLE prompts ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE prompts ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+
+UPDATE prompts SET
+    revision = version,
+    updated_at = COALESCE(
+        (SELECT MAX(v.created_at) FROM prompt_versions v WHERE v.prompt_id = prompts.id),
+        created_at
+    );
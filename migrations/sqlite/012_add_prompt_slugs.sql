@@ -0,0 +1,49 @@
+-- A readable handle for prompts, unique within a workspace. Trashed prompts
+-- keep theirs so restoring never collides.
+ALTER TABLE prompts ADD COLUMN slug TEXT NOT NULL DEFAULT '';
+
+-- Existing prompts get a slug from their title, as in the Postgres migration;
+-- repeats are told apart by id. Without regular expressions, titles are
+-- walked one character at a time, each run of anything but [a-z0-9]
+-- becoming a single dash.
+WITH RECURSIVE walk (id, rest, slug) AS (
+    SELECT id, lower(title), '' FROM prompts
+    UNION ALL
+    SELECT id, substr(rest, 2), CASE
+            WHEN substr(rest, 1, 1) BETWEEN 'a' AND 'z' OR substr(rest, 1, 1) BETWEEN '0' AND '9'
+                THEN slug || substr(rest, 1, 1)
+            WHEN substr(slug, -1) = '-' THEN slug
+            ELSE slug || '-'
+        END
+    FROM walk
+    WHERE rest <> ''
+),
+bases AS (
+    SELECT p.id, p.workspace_id, p.created_at,
+        COALESCE(NULLIF(trim(substr(w.slug, 1, 56), '-'), ''), 'prompt') AS base
+    FROM walk w JOIN prompts p ON p.id = w.id
+    WHERE w.rest = ''
+),
+numbered AS (
+    SELECT id, base, row_number() OVER (PARTITION BY workspace_id, base ORDER BY created_at, id) AS n
+    FROM bases
+)
+UPDATE prompts SET slug = CASE
+        WHEN numbered.n = 1 THEN numbered.base
+        ELSE numbered.base || '-' || substr(lower(hex(prompts.id)), 1, 8)
+    END
+FROM numbered
+WHERE numbered.id = prompts.id;
+
+CREATE UNIQUE INDEX prompts_workspace_slug_idx ON prompts (workspace_id, slug);
+
+-- Slugs prompts used to have, so old references keep resolving
+CREATE TABLE prompt_slug_redirects (
+    workspace_id BLOB NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    slug TEXT NOT NULL,
+    prompt_id BLOB NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (workspace_id, slug)
+);
+
+CREATE INDEX prompt_slug_redirects_prompt_id_idx ON prompt_slug_redirects (prompt_id);
@@ -0,0 +1,265 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use std::marker::PhantomData;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::store::StoreError;
+use crate::AppState;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: Uuid,
+    exp: i64,
+}
+
+// Issues and verifies `<claims>.<signature>` bearer tokens signed with HMAC-SHA256
+pub struct TokenSigner {
+    secret: Vec<u8>,
+    ttl: Duration,
+}
+
+impl TokenSigner {
+    pub fn new(secret: impl Into<Vec<u8>>, ttl: Duration) -> Self {
+        Self { secret: secret.into(), ttl }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    pub fn issue(&self, user_id: Uuid) -> (String, DateTime<Utc>) {
+        let expires_at = Utc::now() + self.ttl;
+        let claims = Claims { sub: user_id, exp: expires_at.timestamp() };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("claims serialize"));
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        (format!("{payload}.{signature}"), expires_at)
+    }
+
+    pub fn verify(&self, token: &str) -> Option<Uuid> {
+        let (payload, signature) = token.split_once('.')?;
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?).ok()?;
+
+        let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        (claims.exp > Utc::now().timestamp()).then_some(claims.sub)
+    }
+}
+
+pub const API_KEY_PREFIX: &str = "phk_";
+
+// Returns the plaintext key, which is shown to the user once, and the hash that gets stored
+pub fn generate_api_key() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let key = format!("{API_KEY_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+    let hash = hash_api_key(&key);
+    (key, hash)
+}
+
+// API keys are high-entropy random strings, so a fast hash is sufficient
+pub fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+// Variant names mirror the `resource:action` scope strings
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "prompts:read")]
+    PromptsRead,
+    #[serde(rename = "prompts:write")]
+    PromptsWrite,
+    #[serde(rename = "prompts:delete")]
+    PromptsDelete,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::PromptsRead => "prompts:read",
+            Scope::PromptsWrite => "prompts:write",
+            Scope::PromptsDelete => "prompts:delete",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    Unauthorized,
+    MissingScope(Scope),
+    AdminDisabled,
+    Internal(StoreError),
+}
+
+impl From<AuthError> for ApiError {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::Unauthorized => ApiError::Unauthorized("Missing or invalid bearer token".to_string()),
+            AuthError::MissingScope(scope) => ApiError::forbidden(
+                "missing_scope",
+                format!("API key lacks the {} scope", scope.as_str()),
+            ),
+            AuthError::AdminDisabled => ApiError::forbidden(
+                "admin_disabled",
+                "Admin endpoints are disabled; set auth.admin_token to enable them",
+            ),
+            AuthError::Internal(e) => e.into(),
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        ApiError::from(self).into_response()
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+// The user identified by a valid session token. API keys are not accepted,
+// so endpoints using this extractor are only reachable by interactive logins.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthUser {
+    pub id: Uuid,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        bearer_token(parts)
+            .and_then(|token| state.tokens.verify(token))
+            .map(|id| AuthUser { id })
+            .ok_or(AuthError::Unauthorized)
+    }
+}
+
+// An operator holding `auth.admin_token`. Session tokens and API keys never
+// qualify, whatever the user's workspace roles.
+#[derive(Debug, Clone, Copy)]
+pub struct Operator;
+
+#[async_trait]
+impl FromRequestParts<AppState> for Operator {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(admin_token) = state.admin_token.as_deref() else {
+            return Err(AuthError::AdminDisabled);
+        };
+        let token = bearer_token(parts).ok_or(AuthError::Unauthorized)?;
+
+        // Digests are compared so the time taken doesn't reveal how much of
+        // the token was right
+        if Sha256::digest(token) == Sha256::digest(admin_token) {
+            Ok(Operator)
+        } else {
+            Err(AuthError::Unauthorized)
+        }
+    }
+}
+
+// Type-level scope requirement for the `Authorized` extractor
+pub trait RequiredScope {
+    const SCOPE: Scope;
+}
+
+pub struct PromptsRead;
+pub struct PromptsWrite;
+pub struct PromptsDelete;
+
+impl RequiredScope for PromptsRead {
+    const SCOPE: Scope = Scope::PromptsRead;
+}
+
+impl RequiredScope for PromptsWrite {
+    const SCOPE: Scope = Scope::PromptsWrite;
+}
+
+impl RequiredScope for PromptsDelete {
+    const SCOPE: Scope = Scope::PromptsDelete;
+}
+
+// A caller allowed to act with scope `S`: either a logged-in user, who holds
+// every scope, or an unexpired API key that was granted `S`.
+#[derive(Debug)]
+pub struct Authorized<S> {
+    pub user_id: Uuid,
+    _scope: PhantomData<S>,
+}
+
+#[async_trait]
+impl<S: RequiredScope> FromRequestParts<AppState> for Authorized<S> {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts).ok_or(AuthError::Unauthorized)?;
+
+        if !token.starts_with(API_KEY_PREFIX) {
+            let user_id = state.tokens.verify(token).ok_or(AuthError::Unauthorized)?;
+            return Ok(Authorized { user_id, _scope: PhantomData });
+        }
+
+        let grant = state
+            .accounts
+            .use_api_key(&hash_api_key(token))
+            .await
+            .map_err(AuthError::Internal)?
+            .ok_or(AuthError::Unauthorized)?;
+
+        if !grant.scopes.iter().any(|scope| scope == S::SCOPE.as_str()) {
+            return Err(AuthError::MissingScope(S::SCOPE));
+        }
+
+        Ok(Authorized { user_id: grant.user_id, _scope: PhantomData })
+    }
+}
@@ -0,0 +1,203 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extract::Json;
+use crate::models::{Prompt, PromptLabel};
+
+// A prompt's entity tag is its id and its revision, which every write bumps.
+// The id keeps a URL that comes to name another prompt, such as a reassigned
+// slug, from matching tags cached for the old one.
+pub fn etag(prompt: &Prompt) -> String {
+    format!("\"{}-{}\"", prompt.id.simple(), prompt.revision)
+}
+
+// Reading through a label also depends on where the label points
+pub fn label_etag(prompt: &Prompt, label: &PromptLabel) -> String {
+    format!("\"{}-{}.{}\"", prompt.id.simple(), prompt.revision, label.version)
+}
+
+// A prompt as a response body, tagged with its ETag
+pub struct TaggedPrompt(pub Prompt);
+
+impl IntoResponse for TaggedPrompt {
+    fn into_response(self) -> Response {
+        tagged(&etag(&self.0), Json(self.0))
+    }
+}
+
+pub fn tagged(etag: &str, body: impl IntoResponse) -> Response {
+    with_etag(etag, body.into_response())
+}
+
+pub fn not_modified(etag: &str) -> Response {
+    with_etag(etag, StatusCode::NOT_MODIFIED.into_response())
+}
+
+fn with_etag(etag: &str, mut response: Response) -> Response {
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    response
+}
+
+// `If-Match` on a write. `None` when the header is absent or `*`.
+#[derive(Debug)]
+pub struct IfMatch(Option<Vec<String>>);
+
+impl IfMatch {
+    // The revisions of prompt `id` the client is willing to overwrite. Tags
+    // we never issued, weak ones and those of other prompts match nothing.
+    pub fn revisions(&self, id: Uuid) -> Option<Vec<i64>> {
+        let prefix = format!("{}-", id.simple());
+        let tags = self.0.as_ref()?;
+        Some(
+            tags.iter()
+                .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.strip_prefix(&prefix)?.parse().ok())
+                .collect(),
+        )
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(tags) = entity_tags(&parts.headers, &header::IF_MATCH)? else {
+            return Ok(IfMatch(None));
+        };
+        if tags.iter().any(|tag| tag == "*") {
+            return Ok(IfMatch(None));
+        }
+        Ok(IfMatch(Some(tags)))
+    }
+}
+
+// `If-None-Match` on a read, compared weakly as RFC 9110 asks
+#[derive(Debug)]
+pub struct IfNoneMatch(Option<Vec<String>>);
+
+impl IfNoneMatch {
+    pub fn matches(&self, etag: &str) -> bool {
+        self.0.as_ref().is_some_and(|tags| {
+            tags.iter()
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+        })
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        entity_tags(&parts.headers, &header::IF_NONE_MATCH).map(IfNoneMatch)
+    }
+}
+
+// The comma-separated tags across every occurrence of the header
+fn entity_tags(headers: &HeaderMap, name: &HeaderName) -> Result<Option<Vec<String>>, ApiError> {
+    let mut tags = Vec::new();
+    for value in headers.get_all(name) {
+        let value = value.to_str().map_err(|_| {
+            ApiError::bad_request("invalid_precondition", format!("{name} is not a valid header value"))
+        })?;
+        tags.extend(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(String::from),
+        );
+    }
+    Ok((!tags.is_empty()).then_some(tags))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    const ID: Uuid = Uuid::from_u128(0x7d0c_51e3_94b2_4c4f_a1c8_2e6f_0b93_d415);
+
+    // The tag of revision `revision` of prompt `ID`
+    fn tag(revision: &str) -> String {
+        format!("\"{}-{revision}\"", ID.simple())
+    }
+
+    fn parts(name: HeaderName, values: &[&[u8]]) -> Parts {
+        let mut request = Request::builder();
+        for value in values {
+            request = request.header(&name, HeaderValue::from_bytes(value).unwrap());
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    async fn if_match(values: &[&str]) -> Result<Option<Vec<i64>>, ApiError> {
+        let values: Vec<_> = values.iter().map(|value| value.as_bytes()).collect();
+        let mut parts = parts(header::IF_MATCH, &values);
+        IfMatch::from_request_parts(&mut parts, &()).await.map(|if_match| if_match.revisions(ID))
+    }
+
+    async fn if_none_match(values: &[&str]) -> IfNoneMatch {
+        let values: Vec<_> = values.iter().map(|value| value.as_bytes()).collect();
+        let mut parts = parts(header::IF_NONE_MATCH, &values);
+        IfNoneMatch::from_request_parts(&mut parts, &()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn if_match_lists_the_accepted_revisions() {
+        assert_eq!(if_match(&[]).await.unwrap(), None);
+        assert_eq!(if_match(&["*"]).await.unwrap(), None);
+        assert_eq!(if_match(&[&tag("3")]).await.unwrap(), Some(vec![3]));
+        let several = format!("{} , {}", tag("3"), tag("5"));
+        assert_eq!(if_match(&[&several, &tag("8")]).await.unwrap(), Some(vec![3, 5, 8]));
+    }
+
+    #[tokio::test]
+    async fn if_match_ignores_tags_we_never_issued() {
+        // Weak tags can't satisfy If-Match, and neither can unquoted ones
+        assert_eq!(if_match(&[&format!("W/{}", tag("3"))]).await.unwrap(), Some(vec![]));
+        assert_eq!(if_match(&["3", "\"3\"", &tag("x"), &tag("3.1")]).await.unwrap(), Some(vec![]));
+        assert_eq!(if_match(&[&format!("W/{}, {}", tag("3"), tag("4"))]).await.unwrap(), Some(vec![4]));
+    }
+
+    #[tokio::test]
+    async fn if_match_ignores_other_prompts_tags() {
+        let other = format!("\"{}-3\"", Uuid::nil().simple());
+        assert_eq!(if_match(&[&other]).await.unwrap(), Some(vec![]));
+        assert_eq!(if_match(&[&other, &tag("4")]).await.unwrap(), Some(vec![4]));
+    }
+
+    #[tokio::test]
+    async fn unreadable_headers_are_bad_requests() {
+        let mut parts = parts(header::IF_MATCH, &[b"\"3\xff\""]);
+        let e = IfMatch::from_request_parts(&mut parts, &()).await.unwrap_err();
+        assert!(matches!(e, ApiError::BadRequest { code: "invalid_precondition", .. }));
+    }
+
+    #[tokio::test]
+    async fn if_none_match_compares_weakly() {
+        assert!(!if_none_match(&[]).await.matches(&tag("3")));
+        assert!(if_none_match(&[&tag("3")]).await.matches(&tag("3")));
+        assert!(if_none_match(&[&format!("W/{}", tag("3"))]).await.matches(&tag("3")));
+        assert!(if_none_match(&[&format!("{}, W/{}", tag("2"), tag("3"))]).await.matches(&tag("3")));
+        assert!(if_none_match(&["*"]).await.matches(&tag("3.1")));
+        assert!(!if_none_match(&[&tag("3")]).await.matches(&tag("3.1")));
+        assert!(!if_none_match(&["\"3\""]).await.matches(&tag("3")));
+    }
+}
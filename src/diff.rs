@@ -0,0 +1,301 @@
+// Line diffs between two prompt versions, with word-level emphasis inside
+// changed lines, as JSON hunks or as unified diff text.
+use std::time::Duration;
+
+use axum::http::{header, HeaderMap};
+use serde::Serialize;
+use similar::{ChangeTag, DiffOp, TextDiff};
+use uuid::Uuid;
+
+use crate::models::PromptVersion;
+
+// Unchanged lines kept around each change, as in `diff -u`
+const CONTEXT_LINES: usize = 3;
+
+// Past this, the diff settles for a coarser but still correct result
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+pub const UNIFIED_MIME: &str = "text/x-diff";
+
+#[derive(Debug, Serialize)]
+pub struct PromptDiff {
+    pub prompt_id: Uuid,
+    pub from: i32,
+    pub to: i32,
+    pub title: FieldDiff,
+    pub content: FieldDiff,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldDiff {
+    pub changed: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+// Starts are 1-based line numbers, as in a unified diff header
+#[derive(Debug, Serialize)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<Line>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Line {
+    pub op: Op,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<usize>,
+    pub text: String,
+    // Only for changed lines that have a counterpart on the other side
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<Span>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Span {
+    pub text: String,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+impl From<ChangeTag> for Op {
+    fn from(tag: ChangeTag) -> Self {
+        match tag {
+            ChangeTag::Equal => Op::Equal,
+            ChangeTag::Delete => Op::Delete,
+            ChangeTag::Insert => Op::Insert,
+        }
+    }
+}
+
+pub fn versions(from: &PromptVersion, to: &PromptVersion) -> PromptDiff {
+    PromptDiff {
+        prompt_id: to.prompt_id,
+        from: from.version,
+        to: to.version,
+        title: field(&title_line(from), &title_line(to)),
+        content: field(&from.content, &to.content),
+    }
+}
+
+// The same comparison as `versions`, as a unified diff of both fields
+pub fn unified(from: &PromptVersion, to: &PromptVersion) -> String {
+    let mut text = String::new();
+    for (name, old, new) in [
+        ("title", title_line(from), title_line(to)),
+        ("content", from.content.clone(), to.content.clone()),
+    ] {
+        let diff = lines(&old, &new);
+        let out = diff
+            .unified_diff()
+            .context_radius(CONTEXT_LINES)
+            .header(&format!("a/{name}@v{}", from.version), &format!("b/{name}@v{}", to.version))
+            .to_string();
+        text.push_str(&out);
+    }
+    text
+}
+
+// Whether the client asked for the unified text form
+pub fn wants_unified(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|mime| mime.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case(UNIFIED_MIME))
+}
+
+// Titles are a single line; ending it keeps the text form free of
+// "No newline at end of file" markers
+fn title_line(version: &PromptVersion) -> String {
+    format!("{}\n", version.title)
+}
+
+fn lines<'a>(old: &'a str, new: &'a str) -> TextDiff<'a, 'a, 'a, str> {
+    TextDiff::configure().timeout(TIMEOUT).diff_lines(old, new)
+}
+
+fn field(old: &str, new: &str) -> FieldDiff {
+    let diff = lines(old, new);
+    let hunks: Vec<Hunk> = diff
+        .grouped_ops(CONTEXT_LINES)
+        .into_iter()
+        .filter(|ops| !ops.is_empty())
+        .map(|ops| hunk(&diff, &ops))
+        .collect();
+
+    FieldDiff { changed: !hunks.is_empty(), hunks }
+}
+
+fn hunk<'a>(diff: &'a TextDiff<'a, 'a, 'a, str>, ops: &[DiffOp]) -> Hunk {
+    let (first, last) = (&ops[0], &ops[ops.len() - 1]);
+    let old = first.old_range().start..last.old_range().end;
+    let new = first.new_range().start..last.new_range().end;
+
+    let mut lines = Vec::new();
+    for op in ops {
+        for change in diff.iter_inline_changes(op) {
+            let segments: Vec<(bool, String)> = change
+                .iter_strings_lossy()
+                .map(|(emphasized, text)| (emphasized, text.into_owned()))
+                .collect();
+            let text: String = segments.iter().map(|(_, text)| text.as_str()).collect();
+            let spans = if segments.iter().any(|(emphasized, _)| *emphasized) {
+                segments
+                    .into_iter()
+                    .map(|(changed, text)| Span { text: strip_newline(text), changed })
+                    .filter(|span| !span.text.is_empty())
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            lines.push(Line {
+                op: change.tag().into(),
+                old_line: change.old_index().map(|i| i + 1),
+                new_line: change.new_index().map(|i| i + 1),
+                text: strip_newline(text),
+                spans,
+            });
+        }
+    }
+
+    Hunk {
+        old_start: start(&old),
+        old_lines: old.len(),
+        new_start: start(&new),
+        new_lines: new.len(),
+        lines,
+    }
+}
+
+// An empty range points at the line before it, like `@@ -4,0 +5,2 @@`
+fn start(range: &std::ops::Range<usize>) -> usize {
+    if range.is_empty() {
+        range.start
+    } else {
+        range.start + 1
+    }
+}
+
+fn strip_newline(mut text: String) -> String {
+    if text.ends_with('\n') {
+        text.pop();
+        if text.ends_with('\r') {
+            text.pop();
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn version(version: i32, title: &str, content: &str) -> PromptVersion {
+        PromptVersion {
+            prompt_id: Uuid::nil(),
+            version,
+            title: title.to_string(),
+            content: content.to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn ops(diff: &FieldDiff) -> Vec<(&'static str, &str)> {
+        diff.hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .map(|line| {
+                let op = match line.op {
+                    Op::Equal => " ",
+                    Op::Delete => "-",
+                    Op::Insert => "+",
+                };
+                (op, line.text.as_str())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_text_has_no_hunks() {
+        let diff = field("a\nb\n", "a\nb\n");
+        assert!(!diff.changed);
+        assert!(diff.hunks.is_empty());
+    }
+
+    #[test]
+    fn changed_line_with_word_spans() {
+        let diff = field("Intro\nSummarize the news\nOutro\n", "Intro\nSummarize the weather\nOutro\n");
+        assert!(diff.changed);
+        assert_eq!(
+            ops(&diff),
+            [(" ", "Intro"), ("-", "Summarize the news"), ("+", "Summarize the weather"), (" ", "Outro")]
+        );
+
+        let hunk = &diff.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 3, 1, 3));
+        let deleted = &hunk.lines[1];
+        assert_eq!((deleted.old_line, deleted.new_line), (Some(2), None));
+        let changed: Vec<_> = deleted.spans.iter().filter(|s| s.changed).map(|s| s.text.as_str()).collect();
+        assert_eq!(changed, ["news"]);
+        assert!(hunk.lines[0].spans.is_empty());
+    }
+
+    #[test]
+    fn distant_changes_get_separate_hunks() {
+        let old: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+        let new = old.replace("line 2\n", "line two\n").replace("line 19\n", "line nineteen\n");
+        let diff = field(&old, &new);
+        assert_eq!(diff.hunks.len(), 2);
+        assert_eq!(diff.hunks[1].old_start, 19 - CONTEXT_LINES);
+    }
+
+    #[test]
+    fn insertion_into_empty_text_starts_at_line_zero() {
+        let diff = field("", "first\n");
+        let hunk = &diff.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (0, 0, 1, 1));
+        assert_eq!(ops(&diff), [("+", "first")]);
+    }
+
+    #[test]
+    fn crlf_line_endings_are_stripped() {
+        let diff = field("a\r\n", "b\r\n");
+        assert_eq!(ops(&diff), [("-", "a"), ("+", "b")]);
+    }
+
+    #[test]
+    fn unified_text_covers_only_changed_fields() {
+        let text = unified(&version(1, "Same", "keep\nold\n"), &version(3, "Same", "keep\nnew\n"));
+        assert_eq!(text, "--- a/content@v1\n+++ b/content@v3\n@@ -1,2 +1,2 @@\n keep\n-old\n+new\n");
+
+        let text = unified(&version(1, "Old", "x"), &version(2, "New", "x"));
+        assert!(text.starts_with("--- a/title@v1\n+++ b/title@v2\n"));
+        assert!(!text.contains("content@"));
+    }
+
+    #[test]
+    fn accept_negotiation() {
+        let mut headers = HeaderMap::new();
+        assert!(!wants_unified(&headers));
+        headers.insert(header::ACCEPT, "application/json, Text/X-Diff;q=0.9".parse().unwrap());
+        assert!(wants_unified(&headers));
+        headers.insert(header::ACCEPT, "text/*".parse().unwrap());
+        assert!(!wants_unified(&headers));
+    }
+}
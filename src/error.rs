@@ -0,0 +1,233 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::request_id;
+use crate::store::StoreError;
+use crate::template::RenderError;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+// Things a client can ask for that may not exist
+#[derive(Debug, Clone, Copy)]
+pub enum Resource {
+    Prompt,
+    PromptVersion,
+    ApiKey,
+    User,
+    Member,
+    Workspace,
+    Label,
+}
+
+impl Resource {
+    fn code(self) -> &'static str {
+        match self {
+            Resource::Prompt => "prompt_not_found",
+            Resource::PromptVersion => "prompt_version_not_found",
+            Resource::ApiKey => "api_key_not_found",
+            Resource::User => "user_not_found",
+            Resource::Member => "member_not_found",
+            Resource::Workspace => "workspace_not_found",
+            Resource::Label => "label_not_found",
+        }
+    }
+
+    fn detail(self) -> &'static str {
+        match self {
+            Resource::Prompt => "Prompt not found",
+            Resource::PromptVersion => "Prompt version not found",
+            Resource::ApiKey => "API key not found",
+            Resource::User => "User not found",
+            Resource::Member => "Member not found",
+            Resource::Workspace => "Workspace not found",
+            Resource::Label => "Label not found",
+        }
+    }
+}
+
+// One invalid input. `field` is absent when the problem is the body as a whole.
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: Some(field.to_string()),
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+// Every error a handler can return. Codes are part of the API contract, so
+// existing ones must not change meaning.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest { code: &'static str, detail: String },
+    Unauthorized(String),
+    Forbidden { code: &'static str, detail: String },
+    NotFound(Resource),
+    Conflict(String),
+    PreconditionFailed,
+    MalformedJson(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    Validation(Vec<FieldError>),
+    Unavailable { code: &'static str, detail: String },
+    Render(RenderError),
+    Internal(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl ApiError {
+    pub fn bad_request(code: &'static str, detail: impl Into<String>) -> Self {
+        ApiError::BadRequest { code, detail: detail.into() }
+    }
+
+    pub fn forbidden(code: &'static str, detail: impl Into<String>) -> Self {
+        ApiError::Forbidden { code, detail: detail.into() }
+    }
+
+    pub fn unavailable(code: &'static str, detail: impl Into<String>) -> Self {
+        ApiError::Unavailable { code, detail: detail.into() }
+    }
+
+    pub fn internal(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        ApiError::Internal(e.into())
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest { .. } | ApiError::MalformedJson(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Validation(_) | ApiError::Render(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest { code, .. }
+            | ApiError::Forbidden { code, .. }
+            | ApiError::Unavailable { code, .. } => code,
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::NotFound(resource) => resource.code(),
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PreconditionFailed => "precondition_failed",
+            ApiError::MalformedJson(_) => "malformed_json",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Render(_) => "render_failed",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::Conflict(message) => ApiError::Conflict(message.to_string()),
+            StoreError::Stale => ApiError::PreconditionFailed,
+            StoreError::Backend(e) => ApiError::Internal(e),
+        }
+    }
+}
+
+// RFC 7807 problem details
+#[derive(Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code = self.code();
+        let request_id = request_id::current();
+
+        let mut extensions = Map::new();
+        let detail = match self {
+            ApiError::BadRequest { detail, .. }
+            | ApiError::Forbidden { detail, .. }
+            | ApiError::Unavailable { detail, .. }
+            | ApiError::Unauthorized(detail)
+            | ApiError::Conflict(detail)
+            | ApiError::PayloadTooLarge(detail)
+            | ApiError::UnsupportedMediaType(detail) => detail,
+            ApiError::Validation(errors) => {
+                let errors = serde_json::to_value(errors).unwrap_or_default();
+                extensions.insert("errors".to_string(), errors);
+                "One or more fields are invalid".to_string()
+            }
+            // Same shape as a validation failure, with the parser's complaint
+            // as a body-level error
+            ApiError::MalformedJson(message) => {
+                let errors = vec![FieldError { field: None, code: "syntax".to_string(), message }];
+                let errors = serde_json::to_value(errors).unwrap_or_default();
+                extensions.insert("errors".to_string(), errors);
+                "The request body is not valid JSON".to_string()
+            }
+            ApiError::NotFound(resource) => resource.detail().to_string(),
+            ApiError::PreconditionFailed => {
+                "The resource has changed since the supplied ETag was issued".to_string()
+            }
+            ApiError::Render(e) => {
+                extensions.insert("missing".to_string(), e.missing.into());
+                extensions.insert("unused".to_string(), e.unused.into());
+                "Template variables don't match the supplied values".to_string()
+            }
+            // The cause stays in the server log; clients only get the request id
+            ApiError::Internal(e) => {
+                tracing::error!(request_id = request_id.as_deref(), error = %e, "request failed");
+                "An internal error occurred".to_string()
+            }
+        };
+
+        let problem = Problem {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or_default(),
+            status: status.as_u16(),
+            detail,
+            code,
+            request_id,
+            extensions,
+        };
+
+        let mut response = (status, Json(problem)).into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        if status == StatusCode::UNAUTHORIZED {
+            headers.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+        }
+        response
+    }
+}
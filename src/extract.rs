@@ -0,0 +1,204 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{error::Category, Value};
+use validator::Validate;
+
+use crate::error::{ApiError, FieldError};
+use crate::validation;
+
+// Drop-in for `axum::Json` whose rejections are problem+json like every other
+// error. As a response it behaves exactly like `axum::Json`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = mime_type(&req).is_some_and(|mime| {
+            mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json"))
+        });
+        if !is_json {
+            return Err(ApiError::UnsupportedMediaType(
+                "Expected a request with `Content-Type: application/json`".to_string(),
+            ));
+        }
+
+        read_json(req, state).await.map(Json)
+    }
+}
+
+// The body's media type, lowercased and without parameters
+fn mime_type(req: &Request) -> Option<String> {
+    req.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+}
+
+async fn read_json<T, S>(req: Request, state: &S) -> Result<T, ApiError>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    let bytes = Bytes::from_request(req, state).await.map_err(|e| match e.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => ApiError::PayloadTooLarge(e.body_text()),
+        _ => ApiError::bad_request("unreadable_body", e.body_text()),
+    })?;
+    let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+        match inner.classify() {
+            // Well-formed JSON that doesn't fit the expected shape
+            Category::Data => invalid_at(path, inner.to_string()),
+            Category::Syntax | Category::Eof | Category::Io => ApiError::MalformedJson(inner.to_string()),
+        }
+    })
+}
+
+// Converts a decoded JSON value into `T`, reporting mismatches like a bad body
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ApiError> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        invalid_at(path, e.into_inner().to_string())
+    })
+}
+
+fn invalid_at(path: String, message: String) -> ApiError {
+    ApiError::Validation(vec![FieldError {
+        field: (path != ".").then_some(path),
+        code: "invalid".to_string(),
+        message,
+    }])
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+// Drop-in for `axum::extract::Path` with problem+json rejections
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            // A route whose parameters don't fit `T` is our bug, not the client's
+            Err(e) if e.status().is_server_error() => Err(ApiError::internal(e.body_text())),
+            Err(e) => Err(ApiError::bad_request("invalid_path", e.body_text())),
+        }
+    }
+}
+
+// Drop-in for `axum_extra::extract::Query`, which reads repeated keys into
+// a `Vec`, with problem+json rejections
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        axum_extra::extract::Query::<T>::from_request_parts(parts, state)
+            .await
+            .map(|axum_extra::extract::Query(value)| Query(value))
+            .map_err(|e| ApiError::bad_request("invalid_query", format!("Invalid query string: {e}")))
+    }
+}
+
+// A JSON body that has also passed its `#[validate(...)]` rules
+#[derive(Debug)]
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        value
+            .validate()
+            .map_err(|e| ApiError::Validation(validation::field_errors(&e)))?;
+        Ok(ValidJson(value))
+    }
+}
+
+// A PATCH body, told apart by its media type
+#[derive(Debug)]
+pub enum Patch {
+    // RFC 7396, `application/merge-patch+json`
+    Merge(Value),
+    // RFC 6902, `application/json-patch+json`
+    Json(json_patch::Patch),
+}
+
+impl Patch {
+    // Applies the patch to a copy of `document`. A JSON Patch that can't be
+    // applied (a failed `test`, a missing path) leaves nothing half-done.
+    pub fn apply(&self, document: &Value) -> Result<Value, ApiError> {
+        let mut patched = document.clone();
+        match self {
+            Patch::Merge(patch) => json_patch::merge(&mut patched, patch),
+            Patch::Json(patch) => json_patch::patch(&mut patched, &patch.0).map_err(|e| {
+                ApiError::Validation(vec![FieldError {
+                    field: None,
+                    code: "patch_failed".to_string(),
+                    message: e.to_string(),
+                }])
+            })?,
+        }
+        Ok(patched)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for Patch
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match mime_type(&req).as_deref() {
+            Some("application/merge-patch+json") => read_json(req, state).await.map(Patch::Merge),
+            Some("application/json-patch+json") => read_json(req, state).await.map(Patch::Json),
+            _ => Err(ApiError::UnsupportedMediaType(
+                "Expected a request with `Content-Type: application/merge-patch+json` \
+                 or `application/json-patch+json`"
+                    .to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Semaphore;
+
+use crate::config::ServerConfig;
+use crate::error::ApiError;
+
+// Server-wide guards applied to every request before it reaches a handler
+#[derive(Clone)]
+pub struct Limits {
+    in_flight: Arc<Semaphore>,
+    timeout: Duration,
+    body_limit_bytes: usize,
+}
+
+impl Limits {
+    pub fn new(config: &ServerConfig) -> Self {
+        Limits {
+            in_flight: Arc::new(Semaphore::new(config.max_concurrent_requests)),
+            timeout: Duration::from_secs(config.request_timeout_secs),
+            body_limit_bytes: config.body_limit_bytes,
+        }
+    }
+}
+
+// Sheds load instead of queueing it, so a burst can't pile up requests that
+// would time out anyway. A request cut off by the timeout is dropped
+// mid-flight; any open transaction rolls back.
+pub async fn enforce(State(limits): State<Limits>, request: Request, next: Next) -> Response {
+    // Bodies read through the extractors are capped by `DefaultBodyLimit`;
+    // this turns away declared oversize bodies before auth or any other work
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|length| length > limits.body_limit_bytes as u64) {
+        return ApiError::PayloadTooLarge(format!(
+            "Request bodies are limited to {} bytes",
+            limits.body_limit_bytes
+        ))
+        .into_response();
+    }
+
+    let Ok(_permit) = limits.in_flight.try_acquire() else {
+        return ApiError::unavailable("overloaded", "The server is handling too many requests; retry shortly")
+            .into_response();
+    };
+
+    match tokio::time::timeout(limits.timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => ApiError::unavailable(
+            "request_timeout",
+            format!("The request did not complete within {} seconds", limits.timeout.as_secs()),
+        )
+        .into_response(),
+    }
+}
@@ -1,148 +1,1113 @@
 use axum::{
-    routing::{get, post, delete, put},
+    middleware,
+    routing::{get, post, delete, patch, put},
     Router,
-    Json,
-    extract::{State, Path},
-    http::StatusCode,
+    extract::{DefaultBodyLimit, State, RawQuery},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Redirect, Response},
 };
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 use dotenv::dotenv;
+use metrics_exporter_prometheus::PrometheusHandle;
+use validator::Validate;
+use std::collections::HashMap;
+use std::future::IntoFuture;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
 
-// Database model
-#[derive(Debug, FromRow, Serialize)]
-struct Prompt {
-    id: Uuid,
-    title: String,
-    content: String,
-    created_at: DateTime<Utc>,
-}
+mod auth;
+mod conditional;
+mod config;
+mod diff;
+mod error;
+mod extract;
+mod health;
+mod limits;
+mod models;
+mod pagination;
+mod request_id;
+mod search;
+mod shutdown;
+mod slug;
+mod store;
+mod telemetry;
+mod template;
+#[cfg(test)]
+mod tests;
+mod trash;
+mod validation;
+mod workspace;
+
+use auth::{AuthUser, Operator, PromptsDelete, PromptsRead, PromptsWrite, Scope, TokenSigner};
+use conditional::{IfMatch, IfNoneMatch, TaggedPrompt};
+use config::{Args, Config, ConfigError, ServerConfig};
+use error::{ApiError, ApiResult, FieldError, Resource};
+use extract::{Json, Patch, Path, Query, ValidJson};
+use health::Readiness;
+use limits::Limits;
+use models::{
+    ApiKey, LabelMove, Member, Prompt, PromptLabel, PromptVersion, SearchHit, TagCount, User,
+    WorkspaceMembership,
+};
+use pagination::{Cursor, CursorKey, Page};
+use store::{
+    AccountStore, NewApiKey, NewPrompt, PromptChanges, PromptQuery, PromptStore, SchemaStatus,
+    SchemaStore, SortField, SortOrder, StoreError, TagMatch,
+};
+use telemetry::PromptCountCache;
+use workspace::{Role, WorkspaceMember};
 
 // Request/response models
-#[derive(Debug, Deserialize)]
+// Without a `slug`, one is derived from the title
+#[derive(Debug, Deserialize, Validate)]
 struct CreatePrompt {
+    #[validate(custom(function = "validation::slug"))]
+    slug: Option<String>,
+    #[validate(custom(function = "validation::title"))]
     title: String,
+    #[validate(custom(function = "validation::content"))]
     content: String,
+    #[serde(default)]
+    #[validate(custom(function = "validation::tags"))]
+    tags: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+// Omitting `slug` or `tags` leaves the prompt's existing ones untouched
+#[derive(Debug, Deserialize, Validate)]
 struct UpdatePrompt {
+    #[validate(custom(function = "validation::slug"))]
+    slug: Option<String>,
+    #[validate(custom(function = "validation::title"))]
     title: String,
+    #[validate(custom(function = "validation::content"))]
     content: String,
+    #[validate(custom(function = "validation::tags"))]
+    tags: Option<Vec<String>>,
+}
+
+// The editable fields of a prompt, as the JSON document a PATCH applies to.
+// A new editable field only has to be added here to become patchable.
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct EditablePrompt {
+    #[validate(custom(function = "validation::slug"))]
+    slug: String,
+    #[validate(custom(function = "validation::title"))]
+    title: String,
+    #[validate(custom(function = "validation::content"))]
+    content: String,
+    #[serde(default)]
+    #[validate(custom(function = "validation::tags"))]
+    tags: Vec<String>,
+}
+
+impl EditablePrompt {
+    fn of(prompt: &Prompt) -> Self {
+        EditablePrompt {
+            slug: prompt.slug.clone(),
+            title: prompt.title.clone(),
+            content: prompt.content.clone(),
+            tags: prompt.tags.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListPrompts {
+    #[serde(default)]
+    tag: Vec<String>,
+    #[serde(default, rename = "match")]
+    tag_match: TagMatch,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    #[serde(default)]
+    sort: SortField,
+    #[serde(default)]
+    order: SortOrder,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchPrompts {
+    q: String,
+    limit: Option<i64>,
+}
+
+// Both are version numbers of the same prompt, in either order
+#[derive(Debug, Deserialize)]
+struct DiffVersions {
+    from: i32,
+    to: i32,
+}
+
+// `label` reads the version the label points at instead of the latest
+#[derive(Debug, Deserialize)]
+struct GetPrompt {
+    label: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct MoveLabel {
+    #[validate(range(min = 1, message = "must be a version number"))]
+    version: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Credentials {
+    email: String,
+    password: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LoginResponse {
+    token: String,
+    token_type: &'static str,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct CreateWorkspace {
+    #[validate(custom(function = "validation::workspace_name"))]
+    name: String,
+}
+
+// Adds the user with this email to the workspace, or changes their role
+#[derive(Debug, Deserialize)]
+struct PutMember {
+    email: String,
+    role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateApiKey {
+    name: String,
+    scopes: Vec<Scope>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+// The plaintext key is only ever returned from the create call
+#[derive(Debug, Serialize)]
+struct CreatedApiKey {
+    #[serde(flatten)]
+    api_key: ApiKey,
+    key: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RenderedPrompt {
+    prompt_id: Uuid,
+    version: i32,
+    text: String,
 }
 
 // App state
 #[derive(Clone)]
 struct AppState {
-    db: PgPool,
+    prompts: Arc<dyn PromptStore>,
+    accounts: Arc<dyn AccountStore>,
+    schema: Arc<dyn SchemaStore>,
+    tokens: Arc<TokenSigner>,
+    // `None` when `auth.admin_token` is unset
+    admin_token: Option<Arc<str>>,
+    metrics: PrometheusHandle,
+    prompt_counts: PromptCountCache,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
-    let database_url = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
-    let db = PgPool::connect(&database_url).await?;
-    
+    let config = match load_config() {
+        Ok(Some(config)) => config,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let telemetry = telemetry::init(&config.log, &config.otel)?;
+    let metrics = telemetry::install_metrics()?;
+
+    let stores = store::open(&config.database).await?;
+    store::prepare_schema(stores.schema.as_ref(), config.database.auto_migrate).await?;
+    trash::spawn_purge(stores.prompts.clone(), config.trash.clone());
+
     // Create app state
-    let state = AppState { db };
+    let state = AppState {
+        prompts: stores.prompts,
+        accounts: stores.accounts,
+        schema: stores.schema,
+        tokens: Arc::new(TokenSigner::new(
+            config.auth.token_secret,
+            chrono::Duration::seconds(config.auth.token_ttl_secs),
+        )),
+        admin_token: Some(config.auth.admin_token).filter(|t| !t.is_empty()).map(Arc::from),
+        metrics,
+        prompt_counts: PromptCountCache::default(),
+    };
+
+    // Kept to close the pool once the server has drained
+    let schema = state.schema.clone();
 
-    // Build our application with routes
-    let app = Router::new()
-        .route("/prompts", post(create_prompt))
-        .route("/prompts", get(list_prompts))
-        .route("/prompts/:id", get(get_prompt))
-        .route("/prompts/:id", put(update_prompt))
-        .route("/prompts/:id", delete(delete_prompt))
-        .with_state(state);
+    let app = app(state, &config.server);
 
     // Run the server
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    axum::serve(listener, app).await?;
+    let listener = tokio::net::TcpListener::bind(config.server.bind).await?;
+    tracing::info!(addr = %config.server.bind, "listening");
+
+    // On a signal the listener stops accepting and in-flight requests get
+    // until the deadline to finish
+    let draining = Arc::new(Notify::new());
+    let server = axum::serve(listener, app).with_graceful_shutdown({
+        let draining = draining.clone();
+        async move {
+            shutdown::signal().await;
+            draining.notify_one();
+        }
+    });
+    let deadline = Duration::from_secs(config.server.shutdown_timeout_secs);
+    let drained = tokio::select! {
+        result = server.into_future() => {
+            result?;
+            true
+        }
+        () = async {
+            draining.notified().await;
+            tokio::time::sleep(deadline).await;
+        } => false,
+    };
+
+    // Closing waits for checked-out connections, so after the deadline the
+    // stragglers are left to be dropped with the process
+    if drained {
+        schema.close().await;
+    } else {
+        tracing::warn!("requests still running after the shutdown deadline; stopping anyway");
+    }
+    tracing::info!("shut down");
+    telemetry.shutdown();
 
     Ok(())
 }
 
+// Every route with its middleware, ready to serve
+fn app(state: AppState, server: &ServerConfig) -> Router {
+    Router::new()
+        .route("/auth/register", post(register))
+        .route("/auth/login", post(login))
+        .route("/api-keys", post(create_api_key))
+        .route("/api-keys", get(list_api_keys))
+        .route("/api-keys/:id", delete(delete_api_key))
+        .route("/workspaces", post(create_workspace))
+        .route("/workspaces", get(list_workspaces))
+        .route("/workspaces/:ws/members", get(list_members))
+        .route("/workspaces/:ws/members", put(put_member))
+        .route("/workspaces/:ws/members/:user_id", delete(remove_member))
+        .route("/workspaces/:ws/prompts", post(create_prompt))
+        .route("/workspaces/:ws/prompts", get(list_prompts))
+        .route("/workspaces/:ws/prompts/search", get(search_prompts))
+        .route("/workspaces/:ws/prompts/by-slug/:slug", get(get_prompt_by_slug))
+        .route("/workspaces/:ws/prompts/:id", get(get_prompt))
+        .route("/workspaces/:ws/prompts/:id", put(update_prompt))
+        .route("/workspaces/:ws/prompts/:id", patch(patch_prompt))
+        .route("/workspaces/:ws/prompts/:id", delete(delete_prompt))
+        .route("/workspaces/:ws/prompts/:id/restore", post(restore_prompt))
+        .route("/workspaces/:ws/prompts/:id/render", post(render_prompt))
+        .route("/workspaces/:ws/prompts/:id/versions", get(list_prompt_versions))
+        .route("/workspaces/:ws/prompts/:id/versions/:version", get(get_prompt_version))
+        .route("/workspaces/:ws/prompts/:id/diff", get(diff_prompt_versions))
+        .route("/workspaces/:ws/prompts/:id/labels", get(list_labels))
+        .route("/workspaces/:ws/prompts/:id/labels/:label", get(get_label))
+        .route("/workspaces/:ws/prompts/:id/labels/:label", put(put_label))
+        .route("/workspaces/:ws/prompts/:id/labels/:label/history", get(label_history))
+        .route("/workspaces/:ws/tags", get(list_tags))
+        .route("/workspaces/:ws/trash", get(list_trash))
+        .route("/admin/schema", get(get_schema))
+        .route("/metrics", get(get_metrics))
+        .layer(DefaultBodyLimit::max(server.body_limit_bytes))
+        .layer(middleware::from_fn_with_state(Limits::new(server), limits::enforce))
+        .layer(middleware::from_fn(telemetry::trace_requests))
+        .layer(middleware::from_fn(request_id::middleware))
+        // Added after the layers so frequent probes stay out of request logs
+        // and metrics
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state)
+}
+
+// `None` when the process should exit after `--print-config`
+fn load_config() -> Result<Option<Config>, ConfigError> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    let config = Config::load(args.config.as_deref())?;
+    if args.print_config {
+        print!("{}", config.to_redacted_toml());
+        return Ok(None);
+    }
+
+    config.validate()?;
+    Ok(Some(config))
+}
+
 // Handlers
-async fn create_prompt(
+async fn register(
     State(state): State<AppState>,
-    Json(payload): Json<CreatePrompt>,
-) -> Result<(StatusCode, Json<Prompt>), (StatusCode, String)> {
-    let prompt = sqlx::query_as::<_, Prompt>(
-        "INSERT INTO prompts (title, content) VALUES ($1, $2) RETURNING *"
-    )
-    .bind(&payload.title)
-    .bind(&payload.content)
-    .fetch_one(&state.db)
+    Json(payload): Json<Credentials>,
+) -> ApiResult<(StatusCode, Json<User>)> {
+    let email = payload.email.trim().to_lowercase();
+    if !email.contains('@') {
+        return Err(ApiError::Validation(vec![FieldError::new("email", "email", "must be an email address")]));
+    }
+    if payload.password.chars().count() < 8 {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "password",
+            "length",
+            "must be at least 8 characters",
+        )]));
+    }
+
+    // Argon2 is deliberately slow, keep it off the async workers
+    let password_hash = tokio::task::spawn_blocking(move || auth::hash_password(&payload.password))
+        .await
+        .map_err(ApiError::internal)?
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+
+    let user = state
+        .accounts
+        .create_user(&email, &password_hash)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+async fn login(
+    State(state): State<AppState>,
+    Json(payload): Json<Credentials>,
+) -> ApiResult<Json<LoginResponse>> {
+    let user = state
+        .accounts
+        .find_user_by_email(&payload.email.trim().to_lowercase())
+        .await?;
+
+    let Some(user) = user else {
+        return Err(ApiError::Unauthorized("Invalid email or password".to_string()));
+    };
+
+    let password_hash = user.password_hash.clone();
+    let valid = tokio::task::spawn_blocking(move || {
+        auth::verify_password(&payload.password, &password_hash)
+    })
     .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .map_err(ApiError::internal)?;
+
+    if !valid {
+        return Err(ApiError::Unauthorized("Invalid email or password".to_string()));
+    }
+
+    let (token, expires_at) = state.tokens.issue(user.id);
+    Ok(Json(LoginResponse {
+        token,
+        token_type: "Bearer",
+        expires_at,
+    }))
+}
+
+async fn create_api_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreateApiKey>,
+) -> ApiResult<(StatusCode, Json<CreatedApiKey>)> {
+    if payload.scopes.is_empty() {
+        return Err(ApiError::Validation(vec![FieldError::new("scopes", "length", "must list at least one scope")]));
+    }
+    if payload.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(ApiError::Validation(vec![FieldError::new("expires_at", "past", "must be in the future")]));
+    }
+
+    let (key, key_hash) = auth::generate_api_key();
+    let api_key = state
+        .accounts
+        .create_api_key(NewApiKey {
+            user_id: user.id,
+            name: payload.name,
+            prefix: key[..auth::API_KEY_PREFIX.len() + 8].to_string(),
+            key_hash,
+            scopes: payload.scopes.iter().map(|scope| scope.as_str().to_string()).collect(),
+            expires_at: payload.expires_at,
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { api_key, key })))
+}
+
+async fn list_api_keys(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<Vec<ApiKey>>> {
+    let api_keys = state.accounts.list_api_keys(user.id).await?;
+
+    Ok(Json(api_keys))
+}
+
+async fn delete_api_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let deleted = state.accounts.delete_api_key(user.id, id).await?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(Resource::ApiKey))
+    }
+}
+
+async fn create_workspace(
+    State(state): State<AppState>,
+    user: AuthUser,
+    ValidJson(payload): ValidJson<CreateWorkspace>,
+) -> ApiResult<(StatusCode, Json<WorkspaceMembership>)> {
+    let workspace = state
+        .accounts
+        .create_workspace(payload.name.trim(), user.id)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(WorkspaceMembership { workspace, role: Role::Owner })))
+}
+
+async fn list_workspaces(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<Vec<WorkspaceMembership>>> {
+    let workspaces = state.accounts.list_workspaces(user.id).await?;
+
+    Ok(Json(workspaces))
+}
+
+async fn list_members(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+) -> ApiResult<Json<Vec<Member>>> {
+    let members = state
+        .accounts
+        .list_members(member.workspace_id)
+        .await?;
+
+    Ok(Json(members))
+}
+
+async fn put_member(
+    State(state): State<AppState>,
+    _user: AuthUser,
+    member: WorkspaceMember<PromptsRead>,
+    Json(payload): Json<PutMember>,
+) -> ApiResult<Json<Member>> {
+    // `AuthUser` keeps API keys out of membership management
+    if member.role != Role::Owner {
+        return Err(ApiError::forbidden("insufficient_role", "Only owners can manage members"));
+    }
+
+    let updated = state
+        .accounts
+        .put_member(member.workspace_id, &payload.email.trim().to_lowercase(), payload.role)
+        .await?
+        .ok_or(ApiError::NotFound(Resource::User))?;
+
+    Ok(Json(updated))
+}
+
+async fn remove_member(
+    State(state): State<AppState>,
+    _user: AuthUser,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, user_id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<StatusCode> {
+    // Members may always leave; removing anyone else takes an owner
+    if member.role != Role::Owner && member.user_id != user_id {
+        return Err(ApiError::forbidden("insufficient_role", "Only owners can manage members"));
+    }
+
+    let removed = state
+        .accounts
+        .remove_member(member.workspace_id, user_id)
+        .await?;
+
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(Resource::Member))
+    }
+}
 
-    Ok((StatusCode::CREATED, Json(prompt)))
+// The id is only known once the prompt exists
+#[tracing::instrument(skip_all, fields(prompt_id))]
+async fn create_prompt(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsWrite>,
+    ValidJson(payload): ValidJson<CreatePrompt>,
+) -> ApiResult<(StatusCode, TaggedPrompt)> {
+    let prompt = state
+        .prompts
+        .create_prompt(NewPrompt {
+            workspace_id: member.workspace_id,
+            owner_id: member.user_id,
+            slug: payload.slug,
+            title: payload.title.trim().to_string(),
+            content: payload.content,
+            tags: normalize_tags(payload.tags),
+        })
+        .await?;
+    tracing::Span::current().record("prompt_id", tracing::field::display(prompt.id));
+
+    Ok((StatusCode::CREATED, TaggedPrompt(prompt.with_variables())))
 }
 
 async fn list_prompts(
     State(state): State<AppState>,
-) -> Result<Json<Vec<Prompt>>, (StatusCode, String)> {
-    let prompts = sqlx::query_as::<_, Prompt>("SELECT * FROM prompts")
-        .fetch_all(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    member: WorkspaceMember<PromptsRead>,
+    Query(params): Query<ListPrompts>,
+) -> ApiResult<Json<Page<Prompt>>> {
+    let limit = params
+        .limit
+        .unwrap_or(pagination::DEFAULT_LIMIT)
+        .clamp(1, pagination::MAX_LIMIT);
+    let cursor = match &params.cursor {
+        Some(raw) => Some(
+            Cursor::decode(raw).ok_or(ApiError::bad_request("invalid_cursor", "Invalid cursor"))?,
+        ),
+        None => None,
+    };
+
+    match (params.sort, &cursor) {
+        (_, None)
+        | (SortField::CreatedAt, Some(Cursor { key: CursorKey::CreatedAt(_), .. }))
+        | (SortField::Title, Some(Cursor { key: CursorKey::Title(_), .. })) => {}
+        _ => {
+            return Err(ApiError::bad_request("invalid_cursor", "Cursor does not match sort"));
+        }
+    }
+
+    let query = PromptQuery {
+        tags: normalize_tags(params.tag),
+        tag_match: params.tag_match,
+        sort: params.sort,
+        order: params.order,
+        after: cursor,
+        created_after: params.created_after,
+        created_before: params.created_before,
+        // The extra row only tells us whether another page exists
+        limit: limit + 1,
+    };
+    let mut prompts = state
+        .prompts
+        .list_prompts(member.workspace_id, &query)
+        .await?;
+
+    let next_cursor = if prompts.len() as i64 > limit {
+        prompts.truncate(limit as usize);
+        prompts.last().map(|last| {
+            let key = match params.sort {
+                SortField::CreatedAt => CursorKey::CreatedAt(last.created_at),
+                SortField::Title => CursorKey::Title(last.title.clone()),
+            };
+            Cursor { key, id: last.id }.encode()
+        })
+    } else {
+        None
+    };
 
-    Ok(Json(prompts))
+    Ok(Json(Page {
+        items: prompts.into_iter().map(Prompt::with_variables).collect(),
+        next_cursor,
+    }))
 }
 
+async fn search_prompts(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Query(params): Query<SearchPrompts>,
+) -> ApiResult<Json<Vec<SearchHit>>> {
+    let terms = search::parse(&params.q);
+    if terms.is_empty() {
+        return Err(ApiError::bad_request("invalid_search_query", "Search query has no searchable terms"));
+    }
+    let limit = params
+        .limit
+        .unwrap_or(pagination::DEFAULT_LIMIT)
+        .clamp(1, pagination::MAX_LIMIT);
+
+    let mut hits = state
+        .prompts
+        .search_prompts(member.workspace_id, &terms, limit)
+        .await?;
+
+    for hit in &mut hits {
+        hit.prompt.variables = template::variables(&hit.prompt.content);
+    }
+
+    Ok(Json(hits))
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
 async fn get_prompt(
     State(state): State<AppState>,
-    Path(id): Path<Uuid>,
-) -> Result<Json<Prompt>, (StatusCode, String)> {
-    let prompt = sqlx::query_as::<_, Prompt>("SELECT * FROM prompts WHERE id = $1")
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<GetPrompt>,
+    if_none_match: IfNoneMatch,
+) -> ApiResult<Response> {
+    let prompt = state
+        .prompts
+        .get_prompt(member.workspace_id, id)
+        .await?
+        .ok_or(ApiError::NotFound(Resource::Prompt))?;
 
-    match prompt {
-        Some(p) => Ok(Json(p)),
-        None => Err((StatusCode::NOT_FOUND, "Prompt not found".to_string())),
+    prompt_response(&state, prompt, params, if_none_match).await
+}
+
+#[tracing::instrument(skip_all, fields(slug = %slug, prompt_id))]
+async fn get_prompt_by_slug(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, slug)): Path<(Uuid, String)>,
+    RawQuery(query): RawQuery,
+    Query(params): Query<GetPrompt>,
+    if_none_match: IfNoneMatch,
+) -> ApiResult<Response> {
+    let prompt = state
+        .prompts
+        .get_prompt_by_slug(member.workspace_id, &slug)
+        .await?
+        .ok_or(ApiError::NotFound(Resource::Prompt))?;
+    tracing::Span::current().record("prompt_id", tracing::field::display(prompt.id));
+
+    // Old slugs redirect permanently to the current one
+    if prompt.slug != slug {
+        let mut location = format!("/workspaces/{}/prompts/by-slug/{}", member.workspace_id, prompt.slug);
+        if let Some(query) = query {
+            location.push('?');
+            location.push_str(&query);
+        }
+        return Ok(Redirect::permanent(&location).into_response());
     }
+
+    prompt_response(&state, prompt, params, if_none_match).await
 }
 
+// A single prompt, read through `?label=` if given, honouring If-None-Match
+async fn prompt_response(
+    state: &AppState,
+    prompt: Prompt,
+    params: GetPrompt,
+    if_none_match: IfNoneMatch,
+) -> ApiResult<Response> {
+    let Some(name) = params.label else {
+        let etag = conditional::etag(&prompt);
+        if if_none_match.matches(&etag) {
+            return Ok(conditional::not_modified(&etag));
+        }
+        return Ok(TaggedPrompt(prompt.with_variables()).into_response());
+    };
+
+    // The labelled version's text, with everything else as it is now
+    let label = state
+        .prompts
+        .get_label(prompt.workspace_id, prompt.id, &name)
+        .await?
+        .ok_or(ApiError::NotFound(Resource::Label))?;
+    let etag = conditional::label_etag(&prompt, &label);
+    if if_none_match.matches(&etag) {
+        return Ok(conditional::not_modified(&etag));
+    }
+    let version = state
+        .prompts
+        .get_version(prompt.workspace_id, prompt.id, label.version)
+        .await?
+        .ok_or(ApiError::NotFound(Resource::PromptVersion))?;
+    let prompt = Prompt {
+        title: version.title,
+        content: version.content,
+        version: version.version,
+        ..prompt
+    };
+    Ok(conditional::tagged(&etag, Json(prompt.with_variables())))
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
 async fn update_prompt(
     State(state): State<AppState>,
-    Path(id): Path<Uuid>,
-    Json(payload): Json<UpdatePrompt>,
-) -> Result<Json<Prompt>, (StatusCode, String)> {
-    let prompt = sqlx::query_as::<_, Prompt>(
-        "UPDATE prompts SET title = $1, content = $2 WHERE id = $3 RETURNING *"
-    )
-    .bind(&payload.title)
-    .bind(&payload.content)
-    .bind(id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    member: WorkspaceMember<PromptsWrite>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+    if_match: IfMatch,
+    ValidJson(payload): ValidJson<UpdatePrompt>,
+) -> ApiResult<TaggedPrompt> {
+    let changes = PromptChanges {
+        slug: payload.slug,
+        title: payload.title.trim().to_string(),
+        content: payload.content,
+        tags: payload.tags.map(normalize_tags),
+    };
+    let expected = if_match.revisions(id);
+    let prompt = state
+        .prompts
+        .update_prompt(member.workspace_id, id, changes, expected.as_deref())
+        .await?;
 
     match prompt {
-        Some(p) => Ok(Json(p)),
-        None => Err((StatusCode::NOT_FOUND, "Prompt not found".to_string())),
+        Some(p) => Ok(TaggedPrompt(p.with_variables())),
+        None => Err(ApiError::NotFound(Resource::Prompt)),
     }
 }
 
-async fn delete_prompt(
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn patch_prompt(
     State(state): State<AppState>,
-    Path(id): Path<Uuid>,
-) -> Result<StatusCode, (StatusCode, String)> {
-    let result = sqlx::query("DELETE FROM prompts WHERE id = $1")
-        .bind(id)
-        .execute(&state.db)
+    member: WorkspaceMember<PromptsWrite>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+    if_match: IfMatch,
+    patch: Patch,
+) -> ApiResult<TaggedPrompt> {
+    let prompt = state
+        .prompts
+        .get_prompt(member.workspace_id, id)
+        .await?
+        .ok_or(ApiError::NotFound(Resource::Prompt))?;
+    let expected = if_match.revisions(id);
+    if expected.as_ref().is_some_and(|revisions| !revisions.contains(&prompt.revision)) {
+        return Err(ApiError::PreconditionFailed);
+    }
+
+    let current = EditablePrompt::of(&prompt);
+    let document = serde_json::to_value(&current).map_err(ApiError::internal)?;
+    let mut patched: EditablePrompt = extract::from_value(patch.apply(&document)?)?;
+    patched
+        .validate()
+        .map_err(|e| ApiError::Validation(validation::field_errors(&e)))?;
+    patched.title = patched.title.trim().to_string();
+    patched.tags = normalize_tags(patched.tags);
+
+    // A patch that changes nothing shouldn't mint a new version
+    if patched == current {
+        return Ok(TaggedPrompt(prompt.with_variables()));
+    }
+
+    let changes = PromptChanges {
+        slug: Some(patched.slug),
+        title: patched.title,
+        content: patched.content,
+        tags: Some(patched.tags),
+    };
+    // The patch was applied to this revision, so it must still be current
+    // when written, whether or not the client sent If-Match
+    let prompt = state
+        .prompts
+        .update_prompt(member.workspace_id, id, changes, Some(&[prompt.revision]))
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| match e {
+            StoreError::Stale if expected.is_none() => {
+                ApiError::Conflict("The prompt changed while the patch was being applied".to_string())
+            }
+            e => e.into(),
+        })?;
 
-    if result.rows_affected() == 0 {
-        Err((StatusCode::NOT_FOUND, "Prompt not found".to_string()))
-    } else {
+    match prompt {
+        Some(p) => Ok(TaggedPrompt(p.with_variables())),
+        None => Err(ApiError::NotFound(Resource::Prompt)),
+    }
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn delete_prompt(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsDelete>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+    if_match: IfMatch,
+) -> ApiResult<StatusCode> {
+    let deleted = state
+        .prompts
+        .delete_prompt(member.workspace_id, id, if_match.revisions(id).as_deref())
+        .await?;
+
+    if deleted {
         Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(Resource::Prompt))
+    }
+}
+
+async fn list_trash(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+) -> ApiResult<Json<Vec<Prompt>>> {
+    let prompts = state.prompts.list_trash(member.workspace_id).await?;
+    Ok(Json(prompts.into_iter().map(Prompt::with_variables).collect()))
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn restore_prompt(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsDelete>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<TaggedPrompt> {
+    let prompt = state
+        .prompts
+        .restore_prompt(member.workspace_id, id)
+        .await?;
+
+    match prompt {
+        Some(p) => Ok(TaggedPrompt(p.with_variables())),
+        None => Err(ApiError::NotFound(Resource::Prompt)),
+    }
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn render_prompt(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+    Json(values): Json<HashMap<String, serde_json::Value>>,
+) -> ApiResult<Json<RenderedPrompt>> {
+    let prompt = state
+        .prompts
+        .get_prompt(member.workspace_id, id)
+        .await?
+        .ok_or(ApiError::NotFound(Resource::Prompt))?;
+
+    // Non-string values are substituted as their JSON text
+    let values = values
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(s) => (name, s),
+            other => (name, other.to_string()),
+        })
+        .collect();
+
+    let text = template::render(&prompt.content, &values).map_err(ApiError::Render)?;
+
+    Ok(Json(RenderedPrompt {
+        prompt_id: prompt.id,
+        version: prompt.version,
+        text,
+    }))
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn list_prompt_versions(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<Json<Vec<PromptVersion>>> {
+    let versions = state
+        .prompts
+        .list_versions(member.workspace_id, id)
+        .await?;
+
+    // Every prompt has at least its initial version, so no rows means no prompt
+    if versions.is_empty() {
+        return Err(ApiError::NotFound(Resource::Prompt));
+    }
+
+    Ok(Json(versions))
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn get_prompt_version(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id, version)): Path<(Uuid, Uuid, i32)>,
+) -> ApiResult<Json<PromptVersion>> {
+    let version = state
+        .prompts
+        .get_version(member.workspace_id, id, version)
+        .await?;
+
+    match version {
+        Some(v) => Ok(Json(v)),
+        None => Err(ApiError::NotFound(Resource::PromptVersion)),
+    }
+}
+
+// JSON hunks by default, or a unified diff for `Accept: text/x-diff`
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn diff_prompt_versions(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<DiffVersions>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let from = state.prompts.get_version(member.workspace_id, id, params.from).await?;
+    let to = state.prompts.get_version(member.workspace_id, id, params.to).await?;
+    let (Some(from), Some(to)) = (from, to) else {
+        ensure_prompt_exists(&state, member.workspace_id, id).await?;
+        return Err(ApiError::NotFound(Resource::PromptVersion));
+    };
+
+    let mut response = if diff::wants_unified(&headers) {
+        let content_type = format!("{}; charset=utf-8", diff::UNIFIED_MIME);
+        ([(header::CONTENT_TYPE, content_type)], diff::unified(&from, &to)).into_response()
+    } else {
+        Json(diff::versions(&from, &to)).into_response()
+    };
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("accept"));
+    Ok(response)
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn list_labels(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<Json<Vec<PromptLabel>>> {
+    let labels = state
+        .prompts
+        .list_labels(member.workspace_id, id)
+        .await?;
+
+    if labels.is_empty() {
+        ensure_prompt_exists(&state, member.workspace_id, id).await?;
+    }
+    Ok(Json(labels))
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn get_label(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id, name)): Path<(Uuid, Uuid, String)>,
+) -> ApiResult<Json<PromptLabel>> {
+    let label = state
+        .prompts
+        .get_label(member.workspace_id, id, &name)
+        .await?;
+
+    match label {
+        Some(l) => Ok(Json(l)),
+        None => {
+            ensure_prompt_exists(&state, member.workspace_id, id).await?;
+            Err(ApiError::NotFound(Resource::Label))
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn put_label(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsWrite>,
+    Path((_, id, name)): Path<(Uuid, Uuid, String)>,
+    ValidJson(payload): ValidJson<MoveLabel>,
+) -> ApiResult<Json<PromptLabel>> {
+    if !validation::is_label(&name) {
+        return Err(ApiError::bad_request(
+            "invalid_label",
+            "Labels are up to 64 lowercase letters, digits, `.`, `_` or `-`",
+        ));
     }
-}
\ No newline at end of file
+
+    let label = state
+        .prompts
+        .move_label(member.workspace_id, id, &name, payload.version, member.user_id)
+        .await?;
+
+    match label {
+        Some(l) => Ok(Json(l)),
+        None => {
+            ensure_prompt_exists(&state, member.workspace_id, id).await?;
+            Err(ApiError::Validation(vec![FieldError::new(
+                "version",
+                "unknown_version",
+                "must be an existing version of the prompt",
+            )]))
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(prompt_id = %id))]
+async fn label_history(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+    Path((_, id, name)): Path<(Uuid, Uuid, String)>,
+) -> ApiResult<Json<Vec<LabelMove>>> {
+    let moves = state
+        .prompts
+        .label_history(member.workspace_id, id, &name)
+        .await?;
+
+    if moves.is_empty() {
+        ensure_prompt_exists(&state, member.workspace_id, id).await?;
+    }
+    Ok(Json(moves))
+}
+
+// Tells "nothing here" apart from "no such prompt" for empty lookups
+async fn ensure_prompt_exists(state: &AppState, workspace_id: Uuid, id: Uuid) -> ApiResult<()> {
+    match state.prompts.get_prompt(workspace_id, id).await? {
+        Some(_) => Ok(()),
+        None => Err(ApiError::NotFound(Resource::Prompt)),
+    }
+}
+
+async fn list_tags(
+    State(state): State<AppState>,
+    member: WorkspaceMember<PromptsRead>,
+) -> ApiResult<Json<Vec<TagCount>>> {
+    let tags = state
+        .prompts
+        .list_tags(member.workspace_id)
+        .await?;
+
+    Ok(Json(tags))
+}
+
+async fn get_schema(
+    State(state): State<AppState>,
+    _operator: Operator,
+) -> ApiResult<Json<SchemaStatus>> {
+    let status = state.schema.schema_status().await?;
+
+    Ok(Json(status))
+}
+
+// Liveness: answers as long as the process is serving requests
+async fn healthz() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+// Readiness: 503 with the failing checks until the database can serve traffic
+async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let readiness = health::readiness(state.schema.as_ref()).await;
+    if !readiness.ready {
+        let failing: Vec<_> = readiness.checks.iter().filter(|(_, check)| !check.ok).map(|(name, _)| *name).collect();
+        tracing::warn!(?failing, "not ready");
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(readiness));
+    }
+    (StatusCode::OK, Json(readiness))
+}
+
+// Prometheus text format. Unauthenticated, like most scrape targets; keep the
+// port off the public internet.
+async fn get_metrics(State(state): State<AppState>) -> ApiResult<Response> {
+    let prompts = state.prompt_counts.get(state.prompts.as_ref()).await?;
+    telemetry::record_gauges(prompts, state.schema.pool_stats());
+
+    let content_type = HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8");
+    Ok(([(header::CONTENT_TYPE, content_type)], state.metrics.render()).into_response())
+}
+
+// Trimmed, lowercased, sorted and deduplicated
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .into_iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
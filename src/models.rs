@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::template;
+use crate::workspace::Role;
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Prompt {
+    pub id: Uuid,
+    pub slug: String,
+    pub title: String,
+    pub content: String,
+    pub workspace_id: Uuid,
+    pub owner_id: Option<Uuid>,
+    #[sqlx(skip)]
+    pub variables: Vec<String>,
+    #[sqlx(skip)]
+    pub tags: Vec<String>,
+    pub version: i32,
+    // Bumped by every write, unlike `version` which only moves when the
+    // title or content changes
+    pub revision: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Set while the prompt is in the trash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl Prompt {
+    pub fn with_variables(mut self) -> Self {
+        self.variables = template::variables(&self.content);
+        self
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PromptVersion {
+    pub prompt_id: Uuid,
+    pub version: i32,
+    pub title: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PromptLabel {
+    pub name: String,
+    pub version: i32,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// One move of a label; `from_version` is empty when the label was created
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct LabelMove {
+    pub name: String,
+    pub from_version: Option<i32>,
+    pub to_version: i32,
+    pub moved_by: Option<Uuid>,
+    pub moved_by_email: Option<String>,
+    pub moved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct SearchHit {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub prompt: Prompt,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    #[serde(skip)]
+    pub password_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Workspace {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct WorkspaceMembership {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub workspace: Workspace,
+    pub role: Role,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct SchemaMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
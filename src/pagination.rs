@@ -0,0 +1,69 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+// Position of the last row on a page: its sort key plus the id as a tiebreaker
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "sort", content = "value", rename_all = "snake_case")]
+pub enum CursorKey {
+    CreatedAt(DateTime<Utc>),
+    Title(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cursor {
+    #[serde(flatten)]
+    pub key: CursorKey,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor serializes to JSON");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let id = Uuid::new_v4();
+        let created_at: DateTime<Utc> = "2024-05-01T12:30:00.123456Z".parse().unwrap();
+
+        let encoded = Cursor { key: CursorKey::CreatedAt(created_at), id }.encode();
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')));
+        let decoded = Cursor::decode(&encoded).unwrap();
+        assert!(matches!(decoded.key, CursorKey::CreatedAt(at) if at == created_at));
+        assert_eq!(decoded.id, id);
+
+        let title = "Ünïcode \"title\"";
+        let decoded = Cursor::decode(&Cursor { key: CursorKey::Title(title.to_string()), id }.encode()).unwrap();
+        assert!(matches!(decoded.key, CursorKey::Title(t) if t == title));
+    }
+
+    #[test]
+    fn garbage_cursors_are_rejected() {
+        let not_a_cursor = URL_SAFE_NO_PAD.encode(br#"{"sort":"size","value":1}"#);
+        for cursor in ["", "not base64!", "e30", &not_a_cursor] {
+            assert!(Cursor::decode(cursor).is_none(), "{cursor}");
+        }
+    }
+}
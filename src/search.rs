@@ -0,0 +1,191 @@
+// Parses user search input into terms that each storage backend can match.
+//
+// Words are ANDed together, `"quoted text"` becomes a phrase match and a
+// trailing `*` turns a word into a prefix match. Punctuation is dropped so
+// user input can never produce query syntax errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+pub fn parse(input: &str) -> Vec<Term> {
+    let mut terms = Vec::new();
+
+    for (i, part) in input.split('"').enumerate() {
+        // Odd-numbered parts sit between a pair of quotes
+        if i % 2 == 1 {
+            let words = words(part);
+            if !words.is_empty() {
+                terms.push(Term::Phrase(words));
+            }
+            continue;
+        }
+
+        for token in part.split_whitespace() {
+            let prefix = token.ends_with('*');
+            let mut words = words(token);
+            match (words.len(), prefix) {
+                (0, _) => {}
+                (1, true) => terms.push(Term::Prefix(words.remove(0))),
+                (1, false) => terms.push(Term::Word(words.remove(0))),
+                // Tokens like "step-by-step" match as a phrase
+                _ => terms.push(Term::Phrase(words)),
+            }
+        }
+    }
+
+    terms
+}
+
+// Postgres `to_tsquery` syntax for parsed terms
+pub fn to_tsquery(terms: &[Term]) -> String {
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Word(word) => word.clone(),
+            Term::Prefix(word) => format!("{word}:*"),
+            Term::Phrase(words) => format!("({})", words.join(" <-> ")),
+        })
+        .collect::<Vec<_>>()
+        .join(" & ")
+}
+
+// SQLite FTS5 query syntax for parsed terms. Words are purely alphanumeric,
+// so quoting them is enough to keep FTS5 operators out.
+#[cfg(feature = "sqlite")]
+pub fn to_fts5(terms: &[Term]) -> String {
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Word(word) => format!("\"{word}\""),
+            Term::Prefix(word) => format!("\"{word}\"*"),
+            Term::Phrase(words) => format!("\"{}\"", words.join(" ")),
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+// The databases wrap matches in these rather than in `<mark>` tags, so the
+// text around them can be escaped first. Stray ones in prompt text can only
+// ever turn into extra tags.
+pub const MATCH_START: &str = "\u{2}";
+pub const MATCH_END: &str = "\u{3}";
+
+// Highlights are rendered as HTML, so prompt text must not be able to add
+// markup of its own
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Escapes a highlight from the database and turns its match markers into
+// `<mark>` tags
+pub fn mark_matches(text: &str) -> String {
+    escape_html(text).replace(MATCH_START, "<mark>").replace(MATCH_END, "</mark>")
+}
+
+fn words(text: &str) -> Vec<String> {
+    word_spans(text).into_iter().map(|(_, word)| word).collect()
+}
+
+// Lowercased words of `text` with their byte ranges in the original string
+pub fn word_spans(text: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s..i, text[s..i].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s..text.len(), text[s..].to_lowercase()));
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(w: &str) -> Term {
+        Term::Word(w.to_string())
+    }
+
+    fn phrase(words: &[&str]) -> Term {
+        Term::Phrase(words.iter().map(|w| w.to_string()).collect())
+    }
+
+    #[test]
+    fn words_prefixes_and_phrases() {
+        assert_eq!(
+            parse(r#"Summ* "Three  Bullet" step-by-step, NEWS!"#),
+            [Term::Prefix("summ".to_string()), phrase(&["three", "bullet"]), phrase(&["step", "by", "step"]), word("news")]
+        );
+    }
+
+    #[test]
+    fn empty_phrases_and_bare_punctuation_are_dropped() {
+        assert!(parse(r#""" "  !! " * -- "#).is_empty());
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn an_unterminated_quote_runs_to_the_end() {
+        assert_eq!(parse(r#"tone "formal reply"#), [word("tone"), phrase(&["formal", "reply"])]);
+    }
+
+    #[test]
+    fn query_syntax_is_never_passed_through() {
+        let terms = parse("a & !b | c:* (d)");
+        assert_eq!(terms, [word("a"), word("b"), Term::Prefix("c".to_string()), word("d")]);
+        assert_eq!(to_tsquery(&terms), "a & b & c:* & d");
+    }
+
+    #[test]
+    fn tsquery() {
+        let terms = [word("a"), Term::Prefix("b".to_string()), phrase(&["c", "d"])];
+        assert_eq!(to_tsquery(&terms), "a & b:* & (c <-> d)");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn fts5() {
+        let terms = [word("a"), Term::Prefix("b".to_string()), phrase(&["c", "d"])];
+        assert_eq!(to_fts5(&terms), r#""a" AND "b"* AND "c d""#);
+    }
+
+    #[test]
+    fn highlights_are_escaped_before_marking() {
+        assert_eq!(escape_html(r#"<b a="1">&'"#), "&lt;b a=&quot;1&quot;&gt;&amp;&#39;");
+        let text = format!("<script>{MATCH_START}alert{MATCH_END}(1)</script>");
+        assert_eq!(mark_matches(&text), "&lt;script&gt;<mark>alert</mark>(1)&lt;/script&gt;");
+        assert_eq!(mark_matches("<mark>"), "&lt;mark&gt;");
+    }
+
+    #[test]
+    fn word_spans_index_the_original_text() {
+        let text = "Grüße, WELT";
+        let spans = word_spans(text);
+        assert_eq!(spans, [(0..7, "grüße".to_string()), (9..13, "welt".to_string())]);
+        assert_eq!(&text[spans[0].0.clone()], "Grüße");
+    }
+}
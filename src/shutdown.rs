@@ -0,0 +1,31 @@
+use tokio::signal;
+
+// Resolves on the first SIGINT (Ctrl-C) or, on Unix, SIGTERM
+pub async fn signal() {
+    let interrupt = async {
+        if let Err(e) = signal::ctrl_c().await {
+            tracing::error!(error = %e, "listening for SIGINT failed");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "listening for SIGTERM failed");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = interrupt => tracing::info!("received SIGINT, shutting down"),
+        () = terminate => tracing::info!("received SIGTERM, shutting down"),
+    }
+}
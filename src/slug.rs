@@ -0,0 +1,95 @@
+pub const MAX_LEN: usize = 64;
+
+// Generated slugs stop short of the limit to leave room for a `-N` suffix
+const BASE_LEN: usize = 56;
+
+// `Summarize: News (v2)` becomes `summarize-news-v2`. Titles without any
+// ASCII letters or digits fall back to `prompt`.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if slug.len() >= BASE_LEN {
+            break;
+        }
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "prompt".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// Lowercase words of ASCII letters and digits joined by single dashes
+pub fn is_valid(slug: &str) -> bool {
+    slug.len() <= MAX_LEN
+        && !slug.is_empty()
+        && slug
+            .split('-')
+            .all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()))
+}
+
+// `base`, or the first of `base-2`, `base-3`, ... not in `taken`
+pub fn first_free(base: &str, taken: &[String]) -> String {
+    if !taken.iter().any(|slug| slug == base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{base}-{n}"))
+        .find(|candidate| !taken.contains(candidate))
+        .expect("an unused suffix always exists")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_titles() {
+        assert_eq!(slugify("Summarize: News (v2)"), "summarize-news-v2");
+        assert_eq!(slugify("  --Leading and trailing--  "), "leading-and-trailing");
+        assert_eq!(slugify("Café au lait"), "caf-au-lait");
+        assert_eq!(slugify("¿¡!?"), "prompt");
+        assert_eq!(slugify(""), "prompt");
+    }
+
+    #[test]
+    fn long_titles_leave_room_for_a_suffix() {
+        for title in ["a".repeat(100), "abcd ".repeat(30), "abcdefg ".repeat(30)] {
+            let slug = slugify(&title);
+            assert!(slug.len() <= BASE_LEN, "{slug}");
+            assert!(is_valid(&first_free(&slug, std::slice::from_ref(&slug))), "{slug}");
+        }
+    }
+
+    #[test]
+    fn slugify_always_produces_a_valid_slug() {
+        for title in ["Summarize: News (v2)", "x", "--", "A  B", "日本語のタイトル 2"] {
+            assert!(is_valid(&slugify(title)), "{title}");
+        }
+    }
+
+    #[test]
+    fn valid_slugs() {
+        for slug in ["a", "a-b-1", "v2", &"x".repeat(MAX_LEN)] {
+            assert!(is_valid(slug), "{slug}");
+        }
+        for slug in ["", "-a", "a-", "a--b", "A", "a_b", "a b", "é", &"x".repeat(MAX_LEN + 1)] {
+            assert!(!is_valid(slug), "{slug}");
+        }
+    }
+
+    #[test]
+    fn first_free_appends_the_lowest_unused_suffix() {
+        let taken = |slugs: &[&str]| slugs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(first_free("x", &[]), "x");
+        assert_eq!(first_free("x", &taken(&["x-2"])), "x");
+        assert_eq!(first_free("x", &taken(&["x", "x-2", "x-4"])), "x-3");
+    }
+}
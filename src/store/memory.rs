@@ -0,0 +1,781 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{
+    AccountStore, ApiKeyGrant, NewApiKey, NewPrompt, PromptChanges, PromptCounts, PromptQuery,
+    PromptStore, SchemaStatus, SchemaStore, SortField, SortOrder, StoreError, StoreResult, TagMatch,
+    EMAIL_TAKEN, LAST_OWNER, SLUG_TAKEN, now,
+};
+use crate::models::{
+    ApiKey, LabelMove, Member, Prompt, PromptLabel, PromptVersion, SearchHit, TagCount, User,
+    Workspace, WorkspaceMembership,
+};
+use crate::pagination::{Cursor, CursorKey};
+use crate::search::{self, Term};
+use crate::slug;
+use crate::workspace::Role;
+
+// Keeps everything in process memory; data is lost on restart
+#[derive(Default)]
+pub struct MemoryStore {
+    state: RwLock<State>,
+}
+
+#[derive(Default)]
+struct State {
+    prompts: HashMap<Uuid, Prompt>,
+    versions: HashMap<Uuid, Vec<PromptVersion>>,
+    labels: HashMap<(Uuid, String), PromptLabel>,
+    // (workspace id, old slug) to prompt id
+    slug_redirects: HashMap<(Uuid, String), Uuid>,
+    // Oldest first, keyed by prompt id
+    label_moves: Vec<(Uuid, LabelMove)>,
+    users: HashMap<Uuid, User>,
+    api_keys: HashMap<Uuid, StoredApiKey>,
+    workspaces: HashMap<Uuid, Workspace>,
+    members: HashMap<(Uuid, Uuid), (Role, DateTime<Utc>)>,
+}
+
+struct StoredApiKey {
+    api_key: ApiKey,
+    user_id: Uuid,
+    key_hash: String,
+}
+
+impl State {
+    fn owner_count(&self, workspace_id: Uuid) -> usize {
+        self.members
+            .iter()
+            .filter(|((ws, _), (role, _))| *ws == workspace_id && *role == Role::Owner)
+            .count()
+    }
+
+    // Prompts in the trash are left out, as they are for every read
+    fn live_prompt(&self, workspace_id: Uuid, id: Uuid) -> Option<&Prompt> {
+        self.prompts
+            .get(&id)
+            .filter(|p| p.workspace_id == workspace_id && p.deleted_at.is_none())
+    }
+
+    fn live_prompt_mut(&mut self, workspace_id: Uuid, id: Uuid) -> Option<&mut Prompt> {
+        self.prompts
+            .get_mut(&id)
+            .filter(|p| p.workspace_id == workspace_id && p.deleted_at.is_none())
+    }
+
+    fn live_prompts(&self, workspace_id: Uuid) -> impl Iterator<Item = &Prompt> {
+        self.prompts
+            .values()
+            .filter(move |p| p.workspace_id == workspace_id && p.deleted_at.is_none())
+    }
+
+    // Trashed prompts keep their slugs, so they count too
+    fn slug_in_use(&self, workspace_id: Uuid, slug: &str, except: Option<Uuid>) -> bool {
+        self.prompts
+            .values()
+            .any(|p| p.workspace_id == workspace_id && p.slug == slug && Some(p.id) != except)
+    }
+
+    fn free_slug(&self, workspace_id: Uuid, base: &str) -> String {
+        let prefix = format!("{base}-");
+        let taken: Vec<String> = self
+            .prompts
+            .values()
+            .filter(|p| p.workspace_id == workspace_id)
+            .map(|p| &p.slug)
+            .chain(
+                self.slug_redirects
+                    .keys()
+                    .filter(|(ws, _)| *ws == workspace_id)
+                    .map(|(_, slug)| slug),
+            )
+            .filter(|slug| *slug == base || slug.starts_with(&prefix))
+            .cloned()
+            .collect();
+        slug::first_free(base, &taken)
+    }
+
+    fn record_version(&mut self, prompt: &Prompt, created_at: DateTime<Utc>) {
+        self.versions.entry(prompt.id).or_default().push(PromptVersion {
+            prompt_id: prompt.id,
+            version: prompt.version,
+            title: prompt.title.clone(),
+            content: prompt.content.clone(),
+            created_at,
+        });
+    }
+}
+
+fn sort_key_cmp(sort: SortField, a: &Prompt, b: &Prompt) -> Ordering {
+    match sort {
+        SortField::CreatedAt => (a.created_at, a.id).cmp(&(b.created_at, b.id)),
+        SortField::Title => (&a.title, a.id).cmp(&(&b.title, b.id)),
+    }
+}
+
+// Where a prompt sorts relative to the row a cursor points at
+fn cursor_cmp(prompt: &Prompt, cursor: &Cursor) -> Ordering {
+    match &cursor.key {
+        CursorKey::CreatedAt(at) => (prompt.created_at, prompt.id).cmp(&(*at, cursor.id)),
+        CursorKey::Title(title) => (prompt.title.as_str(), prompt.id).cmp(&(title.as_str(), cursor.id)),
+    }
+}
+
+// Byte ranges of `text` matched by a search term
+fn term_matches(spans: &[(Range<usize>, String)], term: &Term) -> Vec<Range<usize>> {
+    match term {
+        Term::Word(word) => spans
+            .iter()
+            .filter(|(_, w)| w == word)
+            .map(|(range, _)| range.clone())
+            .collect(),
+        Term::Prefix(prefix) => spans
+            .iter()
+            .filter(|(_, w)| w.starts_with(prefix.as_str()))
+            .map(|(range, _)| range.clone())
+            .collect(),
+        Term::Phrase(words) => spans
+            .windows(words.len())
+            .filter(|window| window.iter().zip(words).all(|((_, w), word)| w == word))
+            .map(|window| window[0].0.start..window[window.len() - 1].0.end)
+            .collect(),
+    }
+}
+
+fn highlight(text: &str, mut ranges: Vec<Range<usize>>) -> String {
+    ranges.sort_by_key(|range| range.start);
+
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for range in ranges {
+        // Overlapping matches are folded into the previous highlight
+        if range.start < pos {
+            continue;
+        }
+        out.push_str(&search::escape_html(&text[pos..range.start]));
+        out.push_str("<mark>");
+        out.push_str(&search::escape_html(&text[range.clone()]));
+        out.push_str("</mark>");
+        pos = range.end;
+    }
+    out.push_str(&search::escape_html(&text[pos..]));
+    out
+}
+
+const SNIPPET_WORDS: usize = 30;
+
+// A window of content around the first match, in the spirit of ts_headline
+fn snippet(content: &str, spans: &[(Range<usize>, String)], matches: Vec<Range<usize>>) -> String {
+    if spans.is_empty() {
+        return String::new();
+    }
+
+    let first = matches.iter().map(|range| range.start).min().unwrap_or(0);
+    let first_word = spans.iter().position(|(range, _)| range.end > first).unwrap_or(0);
+    let start = first_word.saturating_sub(SNIPPET_WORDS / 3);
+    let end = (start + SNIPPET_WORDS).min(spans.len());
+    let window = spans[start].0.start..spans[end - 1].0.end;
+
+    let inside = matches
+        .into_iter()
+        .filter(|range| range.start >= window.start && range.end <= window.end)
+        .map(|range| range.start - window.start..range.end - window.start)
+        .collect();
+    highlight(&content[window], inside)
+}
+
+#[async_trait]
+impl PromptStore for MemoryStore {
+    async fn create_prompt(&self, new: NewPrompt) -> StoreResult<Prompt> {
+        let mut state = self.state.write().await;
+
+        let slug = match new.slug {
+            Some(slug) if state.slug_in_use(new.workspace_id, &slug, None) => {
+                return Err(StoreError::Conflict(SLUG_TAKEN));
+            }
+            Some(slug) => slug,
+            None => state.free_slug(new.workspace_id, &slug::slugify(&new.title)),
+        };
+        state.slug_redirects.remove(&(new.workspace_id, slug.clone()));
+
+        let created_at = now();
+        let prompt = Prompt {
+            id: Uuid::new_v4(),
+            slug,
+            title: new.title,
+            content: new.content,
+            workspace_id: new.workspace_id,
+            owner_id: Some(new.owner_id),
+            variables: Vec::new(),
+            tags: new.tags,
+            version: 1,
+            revision: 1,
+            created_at,
+            updated_at: created_at,
+            deleted_at: None,
+        };
+
+        state.record_version(&prompt, created_at);
+        state.prompts.insert(prompt.id, prompt.clone());
+        Ok(prompt)
+    }
+
+    async fn list_prompts(&self, workspace_id: Uuid, query: &PromptQuery) -> StoreResult<Vec<Prompt>> {
+        let state = self.state.read().await;
+
+        let forward = match query.order {
+            SortOrder::Asc => Ordering::Greater,
+            SortOrder::Desc => Ordering::Less,
+        };
+        let mut prompts: Vec<&Prompt> = state
+            .live_prompts(workspace_id)
+            .filter(|p| match (query.tags.is_empty(), query.tag_match) {
+                (true, _) => true,
+                (false, TagMatch::Any) => query.tags.iter().any(|t| p.tags.contains(t)),
+                (false, TagMatch::All) => query.tags.iter().all(|t| p.tags.contains(t)),
+            })
+            .filter(|p| query.created_after.is_none_or(|after| p.created_at > after))
+            .filter(|p| query.created_before.is_none_or(|before| p.created_at < before))
+            .filter(|p| query.after.as_ref().is_none_or(|cursor| cursor_cmp(p, cursor) == forward))
+            .collect();
+
+        prompts.sort_by(|a, b| sort_key_cmp(query.sort, a, b));
+        if query.order == SortOrder::Desc {
+            prompts.reverse();
+        }
+
+        Ok(prompts
+            .into_iter()
+            .take(query.limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn search_prompts(
+        &self,
+        workspace_id: Uuid,
+        terms: &[Term],
+        limit: i64,
+    ) -> StoreResult<Vec<SearchHit>> {
+        let state = self.state.read().await;
+
+        let mut hits: Vec<SearchHit> = state
+            .live_prompts(workspace_id)
+            .filter_map(|prompt| {
+                let title_spans = search::word_spans(&prompt.title);
+                let content_spans = search::word_spans(&prompt.content);
+
+                let mut title_matches = Vec::new();
+                let mut content_matches = Vec::new();
+                for term in terms {
+                    let in_title = term_matches(&title_spans, term);
+                    let in_content = term_matches(&content_spans, term);
+                    if in_title.is_empty() && in_content.is_empty() {
+                        return None;
+                    }
+                    title_matches.extend(in_title);
+                    content_matches.extend(in_content);
+                }
+
+                // Title hits weigh more, like the A/B weights of the Postgres index
+                let score = title_matches.len() as f32 + 0.4 * content_matches.len() as f32;
+                Some(SearchHit {
+                    prompt: prompt.clone(),
+                    rank: score / (score + 1.0),
+                    title_highlight: highlight(&prompt.title, title_matches),
+                    snippet: snippet(&prompt.content, &content_spans, content_matches),
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.rank
+                .total_cmp(&a.rank)
+                .then(b.prompt.created_at.cmp(&a.prompt.created_at))
+        });
+        hits.truncate(limit as usize);
+        Ok(hits)
+    }
+
+    async fn get_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        let state = self.state.read().await;
+        Ok(state.live_prompt(workspace_id, id).cloned())
+    }
+
+    async fn get_prompt_by_slug(&self, workspace_id: Uuid, slug: &str) -> StoreResult<Option<Prompt>> {
+        let state = self.state.read().await;
+
+        let prompt = state
+            .live_prompts(workspace_id)
+            .find(|p| p.slug == slug)
+            .or_else(|| {
+                let id = state.slug_redirects.get(&(workspace_id, slug.to_string()))?;
+                state.live_prompt(workspace_id, *id)
+            });
+        Ok(prompt.cloned())
+    }
+
+    async fn update_prompt(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        changes: PromptChanges,
+        expected: Option<&[i64]>,
+    ) -> StoreResult<Option<Prompt>> {
+        let mut state = self.state.write().await;
+
+        let Some(prompt) = state.live_prompt(workspace_id, id) else {
+            return Ok(None);
+        };
+        if expected.is_some_and(|revisions| !revisions.contains(&prompt.revision)) {
+            return Err(StoreError::Stale);
+        }
+        if changes
+            .slug
+            .as_ref()
+            .is_some_and(|slug| state.slug_in_use(workspace_id, slug, Some(id)))
+        {
+            return Err(StoreError::Conflict(SLUG_TAKEN));
+        }
+
+        let Some(prompt) = state.live_prompt_mut(workspace_id, id) else {
+            return Ok(None);
+        };
+
+        let updated_at = now();
+        let renamed = match changes.slug {
+            Some(slug) if slug != prompt.slug => Some(std::mem::replace(&mut prompt.slug, slug)),
+            _ => None,
+        };
+        let content_changed = prompt.title != changes.title || prompt.content != changes.content;
+        prompt.title = changes.title;
+        prompt.content = changes.content;
+        prompt.version += i32::from(content_changed);
+        prompt.revision += 1;
+        prompt.updated_at = updated_at;
+        if let Some(tags) = changes.tags {
+            prompt.tags = tags;
+        }
+
+        let prompt = prompt.clone();
+        if let Some(old_slug) = renamed {
+            state.slug_redirects.remove(&(workspace_id, prompt.slug.clone()));
+            state.slug_redirects.insert((workspace_id, old_slug), id);
+        }
+        if content_changed {
+            state.record_version(&prompt, updated_at);
+        }
+        Ok(Some(prompt))
+    }
+
+    async fn delete_prompt(&self, workspace_id: Uuid, id: Uuid, expected: Option<&[i64]>) -> StoreResult<bool> {
+        let mut state = self.state.write().await;
+
+        let Some(prompt) = state.live_prompt_mut(workspace_id, id) else {
+            return Ok(false);
+        };
+        if expected.is_some_and(|revisions| !revisions.contains(&prompt.revision)) {
+            return Err(StoreError::Stale);
+        }
+        prompt.deleted_at = Some(now());
+        prompt.revision += 1;
+        Ok(true)
+    }
+
+    async fn list_trash(&self, workspace_id: Uuid) -> StoreResult<Vec<Prompt>> {
+        let state = self.state.read().await;
+
+        let mut prompts: Vec<Prompt> = state
+            .prompts
+            .values()
+            .filter(|p| p.workspace_id == workspace_id && p.deleted_at.is_some())
+            .cloned()
+            .collect();
+        prompts.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.id.cmp(&b.id)));
+        Ok(prompts)
+    }
+
+    async fn restore_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        let mut state = self.state.write().await;
+
+        let Some(prompt) = state
+            .prompts
+            .get_mut(&id)
+            .filter(|p| p.workspace_id == workspace_id && p.deleted_at.is_some())
+        else {
+            return Ok(None);
+        };
+        prompt.deleted_at = None;
+        prompt.revision += 1;
+        Ok(Some(prompt.clone()))
+    }
+
+    async fn purge_deleted(&self, cutoff: DateTime<Utc>) -> StoreResult<u64> {
+        let mut state = self.state.write().await;
+
+        let purged: Vec<Uuid> = state
+            .prompts
+            .values()
+            .filter(|p| p.deleted_at.is_some_and(|at| at < cutoff))
+            .map(|p| p.id)
+            .collect();
+        for id in &purged {
+            state.prompts.remove(id);
+            state.versions.remove(id);
+        }
+        state.labels.retain(|(prompt_id, _), _| !purged.contains(prompt_id));
+        state.label_moves.retain(|(prompt_id, _)| !purged.contains(prompt_id));
+        state.slug_redirects.retain(|_, prompt_id| !purged.contains(prompt_id));
+        Ok(purged.len() as u64)
+    }
+
+    async fn count_prompts(&self) -> StoreResult<PromptCounts> {
+        let state = self.state.read().await;
+
+        let trashed = state.prompts.values().filter(|p| p.deleted_at.is_some()).count() as i64;
+        Ok(PromptCounts {
+            live: state.prompts.len() as i64 - trashed,
+            trashed,
+        })
+    }
+
+    async fn list_versions(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptVersion>> {
+        let state = self.state.read().await;
+
+        if state.live_prompt(workspace_id, id).is_none() {
+            return Ok(Vec::new());
+        }
+        Ok(state.versions.get(&id).cloned().unwrap_or_default())
+    }
+
+    async fn get_version(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        version: i32,
+    ) -> StoreResult<Option<PromptVersion>> {
+        Ok(self
+            .list_versions(workspace_id, id)
+            .await?
+            .into_iter()
+            .find(|v| v.version == version))
+    }
+
+    async fn list_tags(&self, workspace_id: Uuid) -> StoreResult<Vec<TagCount>> {
+        let state = self.state.read().await;
+
+        let mut counts: HashMap<&str, i64> = HashMap::new();
+        for prompt in state.live_prompts(workspace_id) {
+            for tag in &prompt.tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+
+        let mut tags: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(name, count)| TagCount { name: name.to_string(), count })
+            .collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(tags)
+    }
+
+    async fn list_labels(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptLabel>> {
+        let state = self.state.read().await;
+
+        if state.live_prompt(workspace_id, id).is_none() {
+            return Ok(Vec::new());
+        }
+        let mut labels: Vec<PromptLabel> = state
+            .labels
+            .iter()
+            .filter(|((prompt_id, _), _)| *prompt_id == id)
+            .map(|(_, label)| label.clone())
+            .collect();
+        labels.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(labels)
+    }
+
+    async fn get_label(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Option<PromptLabel>> {
+        let state = self.state.read().await;
+
+        if state.live_prompt(workspace_id, id).is_none() {
+            return Ok(None);
+        }
+        Ok(state.labels.get(&(id, name.to_string())).cloned())
+    }
+
+    async fn move_label(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        name: &str,
+        version: i32,
+        moved_by: Uuid,
+    ) -> StoreResult<Option<PromptLabel>> {
+        let mut state = self.state.write().await;
+
+        let version_exists = state
+            .versions
+            .get(&id)
+            .is_some_and(|versions| versions.iter().any(|v| v.version == version));
+        if state.live_prompt(workspace_id, id).is_none() || !version_exists {
+            return Ok(None);
+        }
+
+        let key = (id, name.to_string());
+        let from_version = state.labels.get(&key).map(|label| label.version);
+        if from_version == Some(version) {
+            return Ok(state.labels.get(&key).cloned());
+        }
+
+        let moved_at = now();
+        let label = PromptLabel {
+            name: name.to_string(),
+            version,
+            updated_by: Some(moved_by),
+            updated_at: moved_at,
+        };
+        state.labels.insert(key, label.clone());
+        state.label_moves.push((id, LabelMove {
+            name: name.to_string(),
+            from_version,
+            to_version: version,
+            moved_by: Some(moved_by),
+            moved_by_email: None,
+            moved_at,
+        }));
+        Ok(Some(label))
+    }
+
+    async fn label_history(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Vec<LabelMove>> {
+        let state = self.state.read().await;
+
+        if state.live_prompt(workspace_id, id).is_none() {
+            return Ok(Vec::new());
+        }
+        Ok(state
+            .label_moves
+            .iter()
+            .filter(|(prompt_id, m)| *prompt_id == id && m.name == name)
+            .map(|(_, m)| LabelMove {
+                moved_by_email: m
+                    .moved_by
+                    .and_then(|user_id| state.users.get(&user_id))
+                    .map(|user| user.email.clone()),
+                ..m.clone()
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl AccountStore for MemoryStore {
+    async fn create_user(&self, email: &str, password_hash: &str) -> StoreResult<User> {
+        let mut state = self.state.write().await;
+
+        if state.users.values().any(|u| u.email == email) {
+            return Err(StoreError::Conflict(EMAIL_TAKEN));
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            password_hash: password_hash.to_string(),
+            created_at: now(),
+        };
+        state.users.insert(user.id, user.clone());
+        Ok(user)
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>> {
+        let state = self.state.read().await;
+        Ok(state.users.values().find(|u| u.email == email).cloned())
+    }
+
+    async fn create_api_key(&self, key: NewApiKey) -> StoreResult<ApiKey> {
+        let mut state = self.state.write().await;
+
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            name: key.name,
+            prefix: key.prefix,
+            scopes: key.scopes,
+            created_at: now(),
+            last_used_at: None,
+            expires_at: key.expires_at,
+        };
+        state.api_keys.insert(
+            api_key.id,
+            StoredApiKey {
+                api_key: api_key.clone(),
+                user_id: key.user_id,
+                key_hash: key.key_hash,
+            },
+        );
+        Ok(api_key)
+    }
+
+    async fn list_api_keys(&self, user_id: Uuid) -> StoreResult<Vec<ApiKey>> {
+        let state = self.state.read().await;
+
+        let mut api_keys: Vec<ApiKey> = state
+            .api_keys
+            .values()
+            .filter(|stored| stored.user_id == user_id)
+            .map(|stored| stored.api_key.clone())
+            .collect();
+        api_keys.sort_by_key(|key| key.created_at);
+        Ok(api_keys)
+    }
+
+    async fn delete_api_key(&self, user_id: Uuid, id: Uuid) -> StoreResult<bool> {
+        let mut state = self.state.write().await;
+
+        if state.api_keys.get(&id).is_none_or(|stored| stored.user_id != user_id) {
+            return Ok(false);
+        }
+        state.api_keys.remove(&id);
+        Ok(true)
+    }
+
+    async fn use_api_key(&self, key_hash: &str) -> StoreResult<Option<ApiKeyGrant>> {
+        let mut state = self.state.write().await;
+        let now = now();
+
+        let Some(stored) = state
+            .api_keys
+            .values_mut()
+            .find(|stored| stored.key_hash == key_hash)
+            .filter(|stored| stored.api_key.expires_at.is_none_or(|at| at > now))
+        else {
+            return Ok(None);
+        };
+
+        stored.api_key.last_used_at = Some(now);
+        Ok(Some(ApiKeyGrant {
+            user_id: stored.user_id,
+            scopes: stored.api_key.scopes.clone(),
+        }))
+    }
+
+    async fn create_workspace(&self, name: &str, owner_id: Uuid) -> StoreResult<Workspace> {
+        let mut state = self.state.write().await;
+
+        let workspace = Workspace {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            created_at: now(),
+        };
+        state.workspaces.insert(workspace.id, workspace.clone());
+        state
+            .members
+            .insert((workspace.id, owner_id), (Role::Owner, workspace.created_at));
+        Ok(workspace)
+    }
+
+    async fn list_workspaces(&self, user_id: Uuid) -> StoreResult<Vec<WorkspaceMembership>> {
+        let state = self.state.read().await;
+
+        let mut workspaces: Vec<WorkspaceMembership> = state
+            .members
+            .iter()
+            .filter(|((_, member), _)| *member == user_id)
+            .filter_map(|((ws, _), (role, _))| {
+                state.workspaces.get(ws).map(|workspace| WorkspaceMembership {
+                    workspace: workspace.clone(),
+                    role: *role,
+                })
+            })
+            .collect();
+        workspaces.sort_by(|a, b| a.workspace.name.cmp(&b.workspace.name));
+        Ok(workspaces)
+    }
+
+    async fn member_role(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<Option<Role>> {
+        let state = self.state.read().await;
+        Ok(state.members.get(&(workspace_id, user_id)).map(|(role, _)| *role))
+    }
+
+    async fn list_members(&self, workspace_id: Uuid) -> StoreResult<Vec<Member>> {
+        let state = self.state.read().await;
+
+        let mut members: Vec<Member> = state
+            .members
+            .iter()
+            .filter(|((ws, _), _)| *ws == workspace_id)
+            .filter_map(|((_, user_id), (role, created_at))| {
+                state.users.get(user_id).map(|user| Member {
+                    user_id: user.id,
+                    email: user.email.clone(),
+                    role: *role,
+                    created_at: *created_at,
+                })
+            })
+            .collect();
+        members.sort_by(|a, b| a.email.cmp(&b.email));
+        Ok(members)
+    }
+
+    async fn put_member(&self, workspace_id: Uuid, email: &str, role: Role) -> StoreResult<Option<Member>> {
+        let mut state = self.state.write().await;
+
+        let Some(user) = state.users.values().find(|u| u.email == email).cloned() else {
+            return Ok(None);
+        };
+
+        let key = (workspace_id, user.id);
+        let current = state.members.get(&key).copied();
+        if current.is_some_and(|(r, _)| r == Role::Owner)
+            && role != Role::Owner
+            && state.owner_count(workspace_id) == 1
+        {
+            return Err(StoreError::Conflict(LAST_OWNER));
+        }
+
+        let created_at = current.map_or_else(now, |(_, created_at)| created_at);
+        state.members.insert(key, (role, created_at));
+        Ok(Some(Member {
+            user_id: user.id,
+            email: user.email,
+            role,
+            created_at,
+        }))
+    }
+
+    async fn remove_member(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<bool> {
+        let mut state = self.state.write().await;
+
+        let key = (workspace_id, user_id);
+        match state.members.get(&key) {
+            None => return Ok(false),
+            Some((Role::Owner, _)) if state.owner_count(workspace_id) == 1 => {
+                return Err(StoreError::Conflict(LAST_OWNER));
+            }
+            Some(_) => {}
+        }
+        state.members.remove(&key);
+        Ok(true)
+    }
+}
+
+// Nothing to migrate; the schema is whatever this binary defines
+#[async_trait]
+impl SchemaStore for MemoryStore {
+    async fn schema_status(&self) -> StoreResult<SchemaStatus> {
+        Ok(SchemaStatus { applied: Vec::new(), pending: Vec::new(), unknown: Vec::new() })
+    }
+
+    async fn migrate(&self) -> StoreResult<()> {
+        Ok(())
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::{MigrateError, Migrator};
+use uuid::Uuid;
+
+use crate::config::DatabaseConfig;
+use crate::models::{
+    ApiKey, LabelMove, Member, Prompt, PromptLabel, PromptVersion, SchemaMigration, SearchHit,
+    TagCount, User, Workspace, WorkspaceMembership,
+};
+use crate::pagination::Cursor;
+use crate::search::Term;
+use crate::workspace::Role;
+
+mod memory;
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod timed;
+
+pub use memory::MemoryStore;
+pub use postgres::PgStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+use timed::Timed;
+
+#[derive(Debug)]
+pub enum StoreError {
+    // The write would violate a uniqueness or integrity rule
+    Conflict(&'static str),
+    // The record exists but isn't at any of the revisions the write expected
+    Stale,
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Conflict(message) => f.write_str(message),
+            StoreError::Stale => f.write_str("the record was modified by another write"),
+            StoreError::Backend(e) => write!(f, "storage error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl StoreError {
+    // The call gave up waiting for a pooled connection
+    pub fn is_pool_timeout(&self) -> bool {
+        matches!(self, StoreError::Backend(e) if matches!(e.downcast_ref(), Some(sqlx::Error::PoolTimedOut)))
+    }
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        StoreError::Backend(Box::new(e))
+    }
+}
+
+impl From<MigrateError> for StoreError {
+    fn from(e: MigrateError) -> Self {
+        StoreError::Backend(Box::new(e))
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    CreatedAt,
+    Title,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+// Tags are expected to be normalized and `after` to match `sort`
+#[derive(Debug)]
+pub struct PromptQuery {
+    pub tags: Vec<String>,
+    pub tag_match: TagMatch,
+    pub sort: SortField,
+    pub order: SortOrder,
+    pub after: Option<Cursor>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+// `slug: None` derives a free slug from the title
+#[derive(Debug)]
+pub struct NewPrompt {
+    pub workspace_id: Uuid,
+    pub owner_id: Uuid,
+    pub slug: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+}
+
+// `slug: None` and `tags: None` keep the prompt's current ones. A new slug
+// leaves the old one redirecting to the prompt.
+#[derive(Debug)]
+pub struct PromptChanges {
+    pub slug: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug)]
+pub struct NewApiKey {
+    pub user_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// What an API key lets its bearer do
+#[derive(Debug)]
+pub struct ApiKeyGrant {
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+// Prompts and everything hanging off them. All lookups are scoped to a
+// workspace, so a prompt id from another workspace behaves as if missing.
+// Deleted prompts sit in the trash and are likewise missing everywhere except
+// the trash methods.
+#[async_trait]
+pub trait PromptStore: Send + Sync {
+    async fn create_prompt(&self, prompt: NewPrompt) -> StoreResult<Prompt>;
+
+    async fn list_prompts(&self, workspace_id: Uuid, query: &PromptQuery) -> StoreResult<Vec<Prompt>>;
+
+    async fn search_prompts(
+        &self,
+        workspace_id: Uuid,
+        terms: &[Term],
+        limit: i64,
+    ) -> StoreResult<Vec<SearchHit>>;
+
+    async fn get_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>>;
+
+    // Also resolves slugs the prompt used to have; compare the returned
+    // prompt's slug to tell the two apart
+    async fn get_prompt_by_slug(&self, workspace_id: Uuid, slug: &str) -> StoreResult<Option<Prompt>>;
+
+    // Bumps the revision. A new title or content also bumps the version and
+    // records it in the prompt's history; slug or tag changes alone don't.
+    // With `expected`, fails as `Stale` unless the prompt is at one of those
+    // revisions; the check and the write are atomic.
+    async fn update_prompt(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        changes: PromptChanges,
+        expected: Option<&[i64]>,
+    ) -> StoreResult<Option<Prompt>>;
+
+    // Moves the prompt to the trash. `expected` as for `update_prompt`.
+    async fn delete_prompt(&self, workspace_id: Uuid, id: Uuid, expected: Option<&[i64]>) -> StoreResult<bool>;
+
+    // Most recently deleted first
+    async fn list_trash(&self, workspace_id: Uuid) -> StoreResult<Vec<Prompt>>;
+
+    // `None` unless the prompt is in the trash
+    async fn restore_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>>;
+
+    // Permanently removes prompts deleted before `cutoff`, in every workspace
+    async fn purge_deleted(&self, cutoff: DateTime<Utc>) -> StoreResult<u64>;
+
+    // Totals across every workspace
+    async fn count_prompts(&self) -> StoreResult<PromptCounts>;
+
+    // Empty when the prompt doesn't exist, as every prompt has a first version
+    async fn list_versions(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptVersion>>;
+
+    async fn get_version(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        version: i32,
+    ) -> StoreResult<Option<PromptVersion>>;
+
+    async fn list_tags(&self, workspace_id: Uuid) -> StoreResult<Vec<TagCount>>;
+
+    // Empty when the prompt doesn't exist
+    async fn list_labels(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptLabel>>;
+
+    async fn get_label(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Option<PromptLabel>>;
+
+    // Points the label at `version`, creating it if needed, and records the
+    // move. `None` when the prompt or the version doesn't exist.
+    async fn move_label(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        name: &str,
+        version: i32,
+        moved_by: Uuid,
+    ) -> StoreResult<Option<PromptLabel>>;
+
+    // Oldest move first
+    async fn label_history(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Vec<LabelMove>>;
+}
+
+// Users, their API keys and workspace memberships
+#[async_trait]
+pub trait AccountStore: Send + Sync {
+    async fn create_user(&self, email: &str, password_hash: &str) -> StoreResult<User>;
+
+    async fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>>;
+
+    async fn create_api_key(&self, key: NewApiKey) -> StoreResult<ApiKey>;
+
+    async fn list_api_keys(&self, user_id: Uuid) -> StoreResult<Vec<ApiKey>>;
+
+    async fn delete_api_key(&self, user_id: Uuid, id: Uuid) -> StoreResult<bool>;
+
+    // Resolves an unexpired key and records that it was used
+    async fn use_api_key(&self, key_hash: &str) -> StoreResult<Option<ApiKeyGrant>>;
+
+    // Creates the workspace with `owner_id` as its first owner
+    async fn create_workspace(&self, name: &str, owner_id: Uuid) -> StoreResult<Workspace>;
+
+    async fn list_workspaces(&self, user_id: Uuid) -> StoreResult<Vec<WorkspaceMembership>>;
+
+    async fn member_role(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<Option<Role>>;
+
+    async fn list_members(&self, workspace_id: Uuid) -> StoreResult<Vec<Member>>;
+
+    // Adds or re-roles the user with this email. `None` if there is no such user.
+    async fn put_member(&self, workspace_id: Uuid, email: &str, role: Role) -> StoreResult<Option<Member>>;
+
+    async fn remove_member(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<bool>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PromptCounts {
+    pub live: i64,
+    pub trashed: i64,
+}
+
+// Connection pool occupancy. sqlx doesn't report how many callers are
+// waiting, but `in_use == max` means new queries queue for a connection;
+// the ones that give up are counted by `Timed`.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+    pub idle: u32,
+    pub in_use: u32,
+    pub max: u32,
+}
+
+impl PoolStats {
+    fn of<DB: sqlx::Database>(pool: &sqlx::Pool<DB>) -> Self {
+        let idle = pool.num_idle() as u32;
+        PoolStats {
+            idle,
+            in_use: pool.size().saturating_sub(idle),
+            max: pool.options().get_max_connections(),
+        }
+    }
+}
+
+// The database's migration history compared to the migrations in this binary
+#[derive(Debug, Serialize)]
+pub struct SchemaStatus {
+    pub applied: Vec<SchemaMigration>,
+    // Embedded migrations the database hasn't run yet
+    pub pending: Vec<i64>,
+    // Migrations this binary doesn't know, i.e. applied by a newer release
+    pub unknown: Vec<i64>,
+}
+
+impl SchemaStatus {
+    fn new(migrator: &Migrator, applied: Vec<SchemaMigration>) -> Self {
+        let pending = migrator
+            .iter()
+            .map(|m| m.version)
+            .filter(|version| !applied.iter().any(|a| a.version == *version))
+            .collect();
+        let unknown = applied
+            .iter()
+            .map(|a| a.version)
+            .filter(|version| !migrator.iter().any(|m| m.version == *version))
+            .collect();
+        SchemaStatus { applied, pending, unknown }
+    }
+}
+
+// Migrations are embedded per backend; backends without a schema report none
+#[async_trait]
+pub trait SchemaStore: Send + Sync {
+    async fn schema_status(&self) -> StoreResult<SchemaStatus>;
+
+    async fn migrate(&self) -> StoreResult<()>;
+
+    // Records migrations that were applied by hand before they were embedded,
+    // so `migrate` doesn't run them again. `true` if anything was recorded.
+    async fn adopt_baseline(&self) -> StoreResult<bool> {
+        Ok(false)
+    }
+
+    // A trivial round trip to the database
+    async fn ping(&self) -> StoreResult<()>;
+
+    // Waits for checked-out connections to be returned, then closes them
+    async fn close(&self) {}
+
+    // `None` for backends without a connection pool
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+pub const EMAIL_TAKEN: &str = "Email is already registered";
+pub const LAST_OWNER: &str = "A workspace must keep at least one owner";
+pub const SLUG_TAKEN: &str = "Slug is already used by another prompt in this workspace";
+
+// For backends that stamp rows themselves. Matches the microsecond precision
+// of Postgres timestamps so every backend returns the same values.
+fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+pub struct Stores {
+    pub prompts: Arc<dyn PromptStore>,
+    pub accounts: Arc<dyn AccountStore>,
+    pub schema: Arc<dyn SchemaStore>,
+}
+
+impl Stores {
+    fn new<S: PromptStore + AccountStore + SchemaStore + 'static>(store: S) -> Self {
+        let store = Arc::new(Timed(store));
+        Stores {
+            prompts: store.clone(),
+            accounts: store.clone(),
+            schema: store,
+        }
+    }
+}
+
+// Picks the backend from the URL scheme: `postgres://`, `sqlite://` or `memory://`
+pub async fn open(config: &DatabaseConfig) -> Result<Stores, Box<dyn std::error::Error>> {
+    let scheme = config.url.split_once(':').map(|(scheme, _)| scheme).unwrap_or_default();
+    match scheme {
+        "postgres" | "postgresql" => Ok(Stores::new(PgStore::connect(config).await?)),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Stores::new(SqliteStore::connect(config).await?)),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => Err("SQLite support requires building with `--features sqlite`".into()),
+        "memory" => Ok(Stores::new(MemoryStore::default())),
+        _ => Err(format!("unsupported DATABASE_URL scheme `{scheme}`").into()),
+    }
+}
+
+// Refuses to run against a schema newer than this binary, then applies pending
+// migrations if `apply` is set
+pub async fn prepare_schema(schema: &dyn SchemaStore, apply: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if schema.adopt_baseline().await? {
+        tracing::info!("found a schema without migration history; recorded migration 1 as already applied");
+    }
+
+    let status = schema.schema_status().await?;
+    if !status.unknown.is_empty() {
+        return Err(format!(
+            "database has migrations {:?} that this build doesn't know about; refusing to serve a newer schema",
+            status.unknown
+        )
+        .into());
+    }
+
+    if status.pending.is_empty() {
+        return Ok(());
+    }
+    if apply {
+        schema.migrate().await?;
+    } else {
+        tracing::warn!(pending = ?status.pending, "database.auto_migrate is off; skipping pending migrations");
+    }
+    Ok(())
+}
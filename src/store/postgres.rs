@@ -0,0 +1,1060 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+use super::{
+    AccountStore, ApiKeyGrant, NewApiKey, NewPrompt, PoolStats, PromptChanges, PromptCounts,
+    PromptQuery, PromptStore, SchemaStatus, SchemaStore, SortField, SortOrder, StoreError,
+    StoreResult, TagMatch, EMAIL_TAKEN, LAST_OWNER, SLUG_TAKEN,
+};
+use crate::config::DatabaseConfig;
+use crate::models::{
+    ApiKey, LabelMove, Member, Prompt, PromptLabel, PromptVersion, SchemaMigration, SearchHit,
+    TagCount, User, Workspace, WorkspaceMembership,
+};
+use crate::pagination::{Cursor, CursorKey};
+use crate::search::{self, Term};
+use crate::slug;
+use crate::workspace::Role;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+// Tries at a derived slug before a create gives up with a conflict
+const SLUG_ATTEMPTS: u32 = 5;
+
+pub struct PgStore {
+    db: PgPool,
+}
+
+impl PgStore {
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let options = PgConnectOptions::from_str(&config.url)?
+            .options([("statement_timeout", format!("{}s", config.statement_timeout_secs))]);
+        let db = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .connect_with(options)
+            .await?;
+        Ok(PgStore { db })
+    }
+
+    // One attempt at `create_prompt`
+    async fn insert_prompt(&self, new: &NewPrompt) -> StoreResult<Prompt> {
+        let mut tx = self.db.begin().await?;
+
+        let slug = match &new.slug {
+            Some(slug) => slug.clone(),
+            None => free_slug(&mut tx, new.workspace_id, &slug::slugify(&new.title)).await?,
+        };
+        let mut prompt = sqlx::query_as::<_, Prompt>(
+            "INSERT INTO prompts (workspace_id, slug, title, content, owner_id) \
+             VALUES ($1, $2, $3, $4, $5) RETURNING *"
+        )
+        .bind(new.workspace_id)
+        .bind(&slug)
+        .bind(&new.title)
+        .bind(&new.content)
+        .bind(new.owner_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(slug_conflict)?;
+
+        claim_slug(&mut tx, new.workspace_id, &slug).await?;
+        record_version(&mut tx, &prompt).await?;
+        set_tags(&mut tx, prompt.id, &new.tags).await?;
+        tx.commit().await?;
+
+        prompt.tags = new.tags.clone();
+        Ok(prompt)
+    }
+}
+
+#[async_trait]
+impl PromptStore for PgStore {
+    async fn create_prompt(&self, new: NewPrompt) -> StoreResult<Prompt> {
+        // A derived slug is picked before the insert, so a concurrent create
+        // can take it first. Pick again rather than fail the request.
+        let mut attempts = 1;
+        loop {
+            match self.insert_prompt(&new).await {
+                Err(StoreError::Conflict(SLUG_TAKEN)) if new.slug.is_none() && attempts < SLUG_ATTEMPTS => {
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn list_prompts(&self, workspace_id: Uuid, params: &PromptQuery) -> StoreResult<Vec<Prompt>> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM prompts WHERE deleted_at IS NULL AND workspace_id = ");
+        query.push_bind(workspace_id);
+
+        if !params.tags.is_empty() {
+            query
+                .push(" AND id IN (SELECT pt.prompt_id FROM prompt_tags pt \
+                       JOIN tags t ON t.id = pt.tag_id WHERE t.name = ANY(")
+                .push_bind(&params.tags)
+                .push(")");
+            if params.tag_match == TagMatch::All {
+                query
+                    .push(" GROUP BY pt.prompt_id HAVING COUNT(*) = ")
+                    .push_bind(params.tags.len() as i64);
+            }
+            query.push(")");
+        }
+        if let Some(after) = params.created_after {
+            query.push(" AND created_at > ").push_bind(after);
+        }
+        if let Some(before) = params.created_before {
+            query.push(" AND created_at < ").push_bind(before);
+        }
+
+        let comparison = match params.order {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+        match &params.after {
+            None => {}
+            Some(Cursor { key: CursorKey::CreatedAt(at), id }) => {
+                query
+                    .push(format_args!(" AND (created_at, id) {comparison} ("))
+                    .push_bind(*at)
+                    .push(", ")
+                    .push_bind(*id)
+                    .push(")");
+            }
+            Some(Cursor { key: CursorKey::Title(title), id }) => {
+                query
+                    .push(format_args!(" AND (title, id) {comparison} ("))
+                    .push_bind(title)
+                    .push(", ")
+                    .push_bind(*id)
+                    .push(")");
+            }
+        }
+
+        let column = match params.sort {
+            SortField::CreatedAt => "created_at",
+            SortField::Title => "title",
+        };
+        let direction = match params.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        query
+            .push(format_args!(" ORDER BY {column} {direction}, id {direction} LIMIT "))
+            .push_bind(params.limit);
+
+        let mut prompts = query.build_query_as::<Prompt>().fetch_all(&self.db).await?;
+        attach_tags(&self.db, &mut prompts).await?;
+        Ok(prompts)
+    }
+
+    async fn search_prompts(
+        &self,
+        workspace_id: Uuid,
+        terms: &[Term],
+        limit: i64,
+    ) -> StoreResult<Vec<SearchHit>> {
+        let mut hits = sqlx::query_as::<_, SearchHit>(
+            "SELECT p.*, ts_rank(p.search_vector, q) AS rank, \
+                 ts_headline('english', p.title, q, 'HighlightAll=true, ' || $4) AS title_highlight, \
+                 ts_headline('english', p.content, q, 'MaxFragments=2, MaxWords=30, MinWords=10, ' || $4) \
+                     AS snippet \
+             FROM prompts p, to_tsquery('english', $1) q \
+             WHERE p.workspace_id = $2 AND p.deleted_at IS NULL AND p.search_vector @@ q \
+             ORDER BY rank DESC, p.created_at DESC \
+             LIMIT $3"
+        )
+        .bind(search::to_tsquery(terms))
+        .bind(workspace_id)
+        .bind(limit)
+        .bind(format!("StartSel={}, StopSel={}", search::MATCH_START, search::MATCH_END))
+        .fetch_all(&self.db)
+        .await?;
+
+        for hit in &mut hits {
+            hit.title_highlight = search::mark_matches(&hit.title_highlight);
+            hit.snippet = search::mark_matches(&hit.snippet);
+        }
+
+        attach_tags(&self.db, hits.iter_mut().map(|hit| &mut hit.prompt)).await?;
+        Ok(hits)
+    }
+
+    async fn get_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "SELECT * FROM prompts WHERE id = $1 AND workspace_id = $2 AND deleted_at IS NULL"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(mut prompt) = prompt else {
+            return Ok(None);
+        };
+        attach_tags(&self.db, [&mut prompt]).await?;
+        Ok(Some(prompt))
+    }
+
+    async fn get_prompt_by_slug(&self, workspace_id: Uuid, slug: &str) -> StoreResult<Option<Prompt>> {
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "SELECT * FROM prompts WHERE workspace_id = $1 AND deleted_at IS NULL AND (slug = $2 \
+             OR id = (SELECT prompt_id FROM prompt_slug_redirects WHERE workspace_id = $1 AND slug = $2))"
+        )
+        .bind(workspace_id)
+        .bind(slug)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(mut prompt) = prompt else {
+            return Ok(None);
+        };
+        attach_tags(&self.db, [&mut prompt]).await?;
+        Ok(Some(prompt))
+    }
+
+    async fn update_prompt(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        changes: PromptChanges,
+        expected: Option<&[i64]>,
+    ) -> StoreResult<Option<Prompt>> {
+        let mut tx = self.db.begin().await?;
+
+        // Locked until the update, so the comparison below still holds then
+        let current = sqlx::query_as::<_, (String, String, String)>(
+            "SELECT slug, title, content FROM prompts WHERE id = $1 AND workspace_id = $2 FOR UPDATE"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let content_changed = current
+            .as_ref()
+            .is_some_and(|(_, title, content)| *title != changes.title || *content != changes.content);
+        let old_slug = current.filter(|_| changes.slug.is_some()).map(|(slug, _, _)| slug);
+
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "UPDATE prompts SET title = $1, content = $2, slug = COALESCE($6, slug), \
+             version = version + $7, revision = revision + 1, updated_at = NOW() \
+             WHERE id = $3 AND workspace_id = $4 AND deleted_at IS NULL \
+             AND ($5::bigint[] IS NULL OR revision = ANY($5)) \
+             RETURNING *"
+        )
+        .bind(&changes.title)
+        .bind(&changes.content)
+        .bind(id)
+        .bind(workspace_id)
+        .bind(expected)
+        .bind(&changes.slug)
+        .bind(i32::from(content_changed))
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(slug_conflict)?;
+
+        let Some(mut prompt) = prompt else {
+            return missing_or_stale(&mut *tx, workspace_id, id, expected).await.map(|()| None);
+        };
+
+        if let Some(old_slug) = old_slug.filter(|old| *old != prompt.slug) {
+            claim_slug(&mut tx, workspace_id, &prompt.slug).await?;
+            sqlx::query(
+                "INSERT INTO prompt_slug_redirects (workspace_id, slug, prompt_id) VALUES ($1, $2, $3) \
+                 ON CONFLICT (workspace_id, slug) DO UPDATE SET prompt_id = EXCLUDED.prompt_id, created_at = NOW()"
+            )
+            .bind(workspace_id)
+            .bind(&old_slug)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if content_changed {
+            record_version(&mut tx, &prompt).await?;
+        }
+        match changes.tags {
+            Some(tags) => {
+                set_tags(&mut tx, prompt.id, &tags).await?;
+                prompt.tags = tags;
+            }
+            None => attach_tags(&mut *tx, [&mut prompt]).await?,
+        }
+        tx.commit().await?;
+
+        Ok(Some(prompt))
+    }
+
+    async fn delete_prompt(&self, workspace_id: Uuid, id: Uuid, expected: Option<&[i64]>) -> StoreResult<bool> {
+        let result = sqlx::query(
+            "UPDATE prompts SET deleted_at = NOW(), revision = revision + 1 \
+             WHERE id = $1 AND workspace_id = $2 AND deleted_at IS NULL \
+             AND ($3::bigint[] IS NULL OR revision = ANY($3))"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .bind(expected)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            return Ok(true);
+        }
+        missing_or_stale(&self.db, workspace_id, id, expected).await.map(|()| false)
+    }
+
+    async fn list_trash(&self, workspace_id: Uuid) -> StoreResult<Vec<Prompt>> {
+        let mut prompts = sqlx::query_as::<_, Prompt>(
+            "SELECT * FROM prompts WHERE workspace_id = $1 AND deleted_at IS NOT NULL \
+             ORDER BY deleted_at DESC, id"
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        attach_tags(&self.db, &mut prompts).await?;
+        Ok(prompts)
+    }
+
+    async fn restore_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "UPDATE prompts SET deleted_at = NULL, revision = revision + 1 \
+             WHERE id = $1 AND workspace_id = $2 AND deleted_at IS NOT NULL RETURNING *"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(mut prompt) = prompt else {
+            return Ok(None);
+        };
+        attach_tags(&self.db, [&mut prompt]).await?;
+        Ok(Some(prompt))
+    }
+
+    async fn purge_deleted(&self, cutoff: DateTime<Utc>) -> StoreResult<u64> {
+        let result = sqlx::query("DELETE FROM prompts WHERE deleted_at < $1")
+            .bind(cutoff)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn count_prompts(&self) -> StoreResult<PromptCounts> {
+        let (live, trashed) = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*) - COUNT(deleted_at), COUNT(deleted_at) FROM prompts"
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(PromptCounts { live, trashed })
+    }
+
+    async fn list_versions(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptVersion>> {
+        let versions = sqlx::query_as::<_, PromptVersion>(
+            "SELECT v.* FROM prompt_versions v JOIN prompts p ON p.id = v.prompt_id \
+             WHERE v.prompt_id = $1 AND p.workspace_id = $2 AND p.deleted_at IS NULL \
+             ORDER BY v.version"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(versions)
+    }
+
+    async fn get_version(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        version: i32,
+    ) -> StoreResult<Option<PromptVersion>> {
+        let version = sqlx::query_as::<_, PromptVersion>(
+            "SELECT v.* FROM prompt_versions v JOIN prompts p ON p.id = v.prompt_id \
+             WHERE v.prompt_id = $1 AND v.version = $2 AND p.workspace_id = $3 \
+             AND p.deleted_at IS NULL"
+        )
+        .bind(id)
+        .bind(version)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(version)
+    }
+
+    async fn list_tags(&self, workspace_id: Uuid) -> StoreResult<Vec<TagCount>> {
+        let tags = sqlx::query_as::<_, TagCount>(
+            "SELECT t.name, COUNT(*) AS count FROM tags t \
+             JOIN prompt_tags pt ON pt.tag_id = t.id \
+             JOIN prompts p ON p.id = pt.prompt_id \
+             WHERE p.workspace_id = $1 AND p.deleted_at IS NULL \
+             GROUP BY t.name ORDER BY t.name"
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(tags)
+    }
+
+    async fn list_labels(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptLabel>> {
+        let labels = sqlx::query_as::<_, PromptLabel>(
+            "SELECT l.* FROM prompt_labels l JOIN prompts p ON p.id = l.prompt_id \
+             WHERE l.prompt_id = $1 AND p.workspace_id = $2 AND p.deleted_at IS NULL \
+             ORDER BY l.name"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(labels)
+    }
+
+    async fn get_label(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Option<PromptLabel>> {
+        let label = sqlx::query_as::<_, PromptLabel>(
+            "SELECT l.* FROM prompt_labels l JOIN prompts p ON p.id = l.prompt_id \
+             WHERE l.prompt_id = $1 AND l.name = $2 AND p.workspace_id = $3 AND p.deleted_at IS NULL"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(label)
+    }
+
+    async fn move_label(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        name: &str,
+        version: i32,
+        moved_by: Uuid,
+    ) -> StoreResult<Option<PromptLabel>> {
+        let mut tx = self.db.begin().await?;
+
+        // Locking the prompt serializes moves, so `from_version` is accurate
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM prompt_versions v \
+             JOIN (SELECT id FROM prompts WHERE id = $1 AND workspace_id = $2 AND deleted_at IS NULL \
+                   FOR UPDATE) p ON p.id = v.prompt_id \
+             WHERE v.version = $3)"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .bind(version)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !exists {
+            return Ok(None);
+        }
+
+        let current = sqlx::query_as::<_, PromptLabel>(
+            "SELECT * FROM prompt_labels WHERE prompt_id = $1 AND name = $2"
+        )
+        .bind(id)
+        .bind(name)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(label) = current.as_ref().filter(|label| label.version == version) {
+            return Ok(Some(label.clone()));
+        }
+
+        let label = sqlx::query_as::<_, PromptLabel>(
+            "INSERT INTO prompt_labels (prompt_id, name, version, updated_by) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (prompt_id, name) DO UPDATE \
+             SET version = EXCLUDED.version, updated_by = EXCLUDED.updated_by, updated_at = NOW() \
+             RETURNING name, version, updated_by, updated_at"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(version)
+        .bind(moved_by)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO prompt_label_moves (prompt_id, name, from_version, to_version, moved_by) \
+             VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(current.map(|label| label.version))
+        .bind(version)
+        .bind(moved_by)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(label))
+    }
+
+    async fn label_history(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Vec<LabelMove>> {
+        let moves = sqlx::query_as::<_, LabelMove>(
+            "SELECT m.name, m.from_version, m.to_version, m.moved_by, u.email AS moved_by_email, m.moved_at \
+             FROM prompt_label_moves m \
+             JOIN prompts p ON p.id = m.prompt_id \
+             LEFT JOIN users u ON u.id = m.moved_by \
+             WHERE m.prompt_id = $1 AND m.name = $2 AND p.workspace_id = $3 AND p.deleted_at IS NULL \
+             ORDER BY m.id"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(moves)
+    }
+}
+
+#[async_trait]
+impl AccountStore for PgStore {
+    async fn create_user(&self, email: &str, password_hash: &str) -> StoreResult<User> {
+        sqlx::query_as::<_, User>(
+            "INSERT INTO users (email, password_hash) VALUES ($1, $2) RETURNING *"
+        )
+        .bind(email)
+        .bind(password_hash)
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                StoreError::Conflict(EMAIL_TAKEN)
+            }
+            e => e.into(),
+        })
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>> {
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn create_api_key(&self, key: NewApiKey) -> StoreResult<ApiKey> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6) RETURNING *"
+        )
+        .bind(key.user_id)
+        .bind(&key.name)
+        .bind(&key.prefix)
+        .bind(&key.key_hash)
+        .bind(&key.scopes)
+        .bind(key.expires_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(api_key)
+    }
+
+    async fn list_api_keys(&self, user_id: Uuid) -> StoreResult<Vec<ApiKey>> {
+        let api_keys = sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys WHERE user_id = $1 ORDER BY created_at"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(api_keys)
+    }
+
+    async fn delete_api_key(&self, user_id: Uuid, id: Uuid) -> StoreResult<bool> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn use_api_key(&self, key_hash: &str) -> StoreResult<Option<ApiKeyGrant>> {
+        let grant = sqlx::query_as::<_, (Uuid, Vec<String>)>(
+            "UPDATE api_keys SET last_used_at = NOW() \
+             WHERE key_hash = $1 AND (expires_at IS NULL OR expires_at > NOW()) \
+             RETURNING user_id, scopes"
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(grant.map(|(user_id, scopes)| ApiKeyGrant { user_id, scopes }))
+    }
+
+    async fn create_workspace(&self, name: &str, owner_id: Uuid) -> StoreResult<Workspace> {
+        let mut tx = self.db.begin().await?;
+
+        let workspace = sqlx::query_as::<_, Workspace>(
+            "INSERT INTO workspaces (name) VALUES ($1) RETURNING *"
+        )
+        .bind(name)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("INSERT INTO workspace_members (workspace_id, user_id, role) VALUES ($1, $2, $3)")
+            .bind(workspace.id)
+            .bind(owner_id)
+            .bind(Role::Owner)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(workspace)
+    }
+
+    async fn list_workspaces(&self, user_id: Uuid) -> StoreResult<Vec<WorkspaceMembership>> {
+        let workspaces = sqlx::query_as::<_, WorkspaceMembership>(
+            "SELECT w.*, m.role FROM workspaces w \
+             JOIN workspace_members m ON m.workspace_id = w.id \
+             WHERE m.user_id = $1 ORDER BY w.name"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(workspaces)
+    }
+
+    async fn member_role(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<Option<Role>> {
+        let role = sqlx::query_scalar::<_, Role>(
+            "SELECT role FROM workspace_members WHERE workspace_id = $1 AND user_id = $2"
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(role)
+    }
+
+    async fn list_members(&self, workspace_id: Uuid) -> StoreResult<Vec<Member>> {
+        let members = sqlx::query_as::<_, Member>(
+            "SELECT m.user_id, u.email, m.role, m.created_at FROM workspace_members m \
+             JOIN users u ON u.id = m.user_id \
+             WHERE m.workspace_id = $1 ORDER BY u.email"
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(members)
+    }
+
+    async fn put_member(&self, workspace_id: Uuid, email: &str, role: Role) -> StoreResult<Option<Member>> {
+        let mut tx = self.db.begin().await?;
+
+        let member = sqlx::query_as::<_, Member>(
+            "WITH upserted AS ( \
+                 INSERT INTO workspace_members (workspace_id, user_id, role) \
+                 SELECT $1, id, $2 FROM users WHERE email = $3 \
+                 ON CONFLICT (workspace_id, user_id) DO UPDATE SET role = EXCLUDED.role \
+                 RETURNING * \
+             ) \
+             SELECT m.user_id, u.email, m.role, m.created_at FROM upserted m \
+             JOIN users u ON u.id = m.user_id"
+        )
+        .bind(workspace_id)
+        .bind(role)
+        .bind(email)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        ensure_owner_remains(&mut tx, workspace_id).await?;
+        tx.commit().await?;
+        Ok(member)
+    }
+
+    async fn remove_member(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<bool> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query("DELETE FROM workspace_members WHERE workspace_id = $1 AND user_id = $2")
+            .bind(workspace_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        ensure_owner_remains(&mut tx, workspace_id).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl SchemaStore for PgStore {
+    async fn schema_status(&self) -> StoreResult<SchemaStatus> {
+        let mut conn = self.db.acquire().await?;
+        conn.ensure_migrations_table().await?;
+
+        let applied = sqlx::query_as::<_, SchemaMigration>(
+            "SELECT version, description, installed_on FROM _sqlx_migrations \
+             WHERE success ORDER BY version"
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(SchemaStatus::new(&MIGRATOR, applied))
+    }
+
+    async fn migrate(&self) -> StoreResult<()> {
+        MIGRATOR.run(&self.db).await?;
+        Ok(())
+    }
+
+    // Before migrations were embedded, 001 was applied by hand. Such a
+    // database has `prompts` but an empty history, and running 001 again
+    // would fail on the existing table.
+    async fn adopt_baseline(&self) -> StoreResult<bool> {
+        let mut conn = self.db.acquire().await?;
+        conn.ensure_migrations_table().await?;
+
+        let (recorded, has_prompts): (bool, bool) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM _sqlx_migrations), to_regclass('prompts') IS NOT NULL"
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if recorded || !has_prompts {
+            return Ok(false);
+        }
+
+        // The embedded checksum, so the migrator accepts the row as its own.
+        // Replicas starting together may race to record it.
+        let baseline = MIGRATOR.iter().next().expect("migration 1 is embedded");
+        let result = sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+             VALUES ($1, $2, TRUE, $3, 0) \
+             ON CONFLICT (version) DO NOTHING"
+        )
+        .bind(baseline.version)
+        .bind(&*baseline.description)
+        .bind(&*baseline.checksum)
+        .execute(&mut *conn)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        sqlx::query("SELECT 1").execute(&self.db).await?;
+        Ok(())
+    }
+
+    async fn close(&self) {
+        self.db.close().await;
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats::of(&self.db))
+    }
+}
+
+// Snapshots the prompt's current title and content as an immutable version row
+async fn record_version(tx: &mut Transaction<'_, Postgres>, prompt: &Prompt) -> StoreResult<()> {
+    sqlx::query(
+        "INSERT INTO prompt_versions (prompt_id, version, title, content) VALUES ($1, $2, $3, $4)"
+    )
+    .bind(prompt.id)
+    .bind(prompt.version)
+    .bind(&prompt.title)
+    .bind(&prompt.content)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Replaces the prompt's tags, creating any tags that don't exist yet
+async fn set_tags(tx: &mut Transaction<'_, Postgres>, prompt_id: Uuid, tags: &[String]) -> StoreResult<()> {
+    sqlx::query("INSERT INTO tags (name) SELECT unnest($1::text[]) ON CONFLICT (name) DO NOTHING")
+        .bind(tags)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query("DELETE FROM prompt_tags WHERE prompt_id = $1")
+        .bind(prompt_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO prompt_tags (prompt_id, tag_id) SELECT $1, id FROM tags WHERE name = ANY($2)"
+    )
+    .bind(prompt_id)
+    .bind(tags)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Loads tags for a batch of prompts in a single query
+async fn attach_tags<'a, 'e, E>(
+    executor: E,
+    prompts: impl IntoIterator<Item = &'a mut Prompt>,
+) -> StoreResult<()>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let prompts: Vec<&mut Prompt> = prompts.into_iter().collect();
+    let ids: Vec<Uuid> = prompts.iter().map(|p| p.id).collect();
+    let rows = sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT pt.prompt_id, t.name FROM prompt_tags pt \
+         JOIN tags t ON t.id = pt.tag_id \
+         WHERE pt.prompt_id = ANY($1) ORDER BY t.name"
+    )
+    .bind(&ids)
+    .fetch_all(executor)
+    .await?;
+
+    let mut by_prompt: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (prompt_id, name) in rows {
+        by_prompt.entry(prompt_id).or_default().push(name);
+    }
+    for prompt in prompts {
+        prompt.tags = by_prompt.remove(&prompt.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+// The first of `base`, `base-2`, ... that no prompt uses or used to use
+async fn free_slug(tx: &mut Transaction<'_, Postgres>, workspace_id: Uuid, base: &str) -> StoreResult<String> {
+    let taken = sqlx::query_scalar::<_, String>(
+        "SELECT slug FROM prompts WHERE workspace_id = $1 AND (slug = $2 OR slug LIKE $3) \
+         UNION ALL \
+         SELECT slug FROM prompt_slug_redirects WHERE workspace_id = $1 AND (slug = $2 OR slug LIKE $3)"
+    )
+    .bind(workspace_id)
+    .bind(base)
+    .bind(format!("{base}-%"))
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(slug::first_free(base, &taken))
+}
+
+// A slug taken by a prompt no longer redirects to whoever had it before
+async fn claim_slug(tx: &mut Transaction<'_, Postgres>, workspace_id: Uuid, slug: &str) -> StoreResult<()> {
+    sqlx::query("DELETE FROM prompt_slug_redirects WHERE workspace_id = $1 AND slug = $2")
+        .bind(workspace_id)
+        .bind(slug)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+// Slugs are the only unique column a prompt write can collide on
+fn slug_conflict(e: sqlx::Error) -> StoreError {
+    match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => StoreError::Conflict(SLUG_TAKEN),
+        e => e.into(),
+    }
+}
+
+// Tells apart why a revision-guarded write matched no row
+async fn missing_or_stale<'e, E>(executor: E, workspace_id: Uuid, id: Uuid, expected: Option<&[i64]>) -> StoreResult<()>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    if expected.is_none() {
+        return Ok(());
+    }
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM prompts WHERE id = $1 AND workspace_id = $2 AND deleted_at IS NULL)"
+    )
+    .bind(id)
+    .bind(workspace_id)
+    .fetch_one(executor)
+    .await?;
+
+    if exists {
+        return Err(StoreError::Stale);
+    }
+    Ok(())
+}
+
+// Membership changes must never leave a workspace without an owner
+async fn ensure_owner_remains(tx: &mut Transaction<'_, Postgres>, workspace_id: Uuid) -> StoreResult<()> {
+    let owners = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM workspace_members WHERE workspace_id = $1 AND role = 'owner'"
+    )
+    .bind(workspace_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if owners == 0 {
+        return Err(StoreError::Conflict(LAST_OWNER));
+    }
+    Ok(())
+}
+
+// Needs a Postgres server: set PROMPTHUB_TEST_DATABASE_URL to run these. Each
+// test works in a schema of its own and drops it afterwards.
+#[cfg(test)]
+mod tests {
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::store::prepare_schema;
+
+    async fn scratch_schema() -> Option<(PgPool, PgStore, String)> {
+        let Ok(url) = std::env::var("PROMPTHUB_TEST_DATABASE_URL") else {
+            eprintln!("PROMPTHUB_TEST_DATABASE_URL is not set; skipping");
+            return None;
+        };
+        let admin = PgPool::connect(&url).await.unwrap();
+        let schema = format!("test_{}", Uuid::new_v4().simple());
+        admin.execute(&*format!("CREATE SCHEMA {schema}")).await.unwrap();
+
+        let options = PgConnectOptions::from_str(&url).unwrap().options([("search_path", &schema)]);
+        let db = PgPoolOptions::new().max_connections(2).connect_with(options).await.unwrap();
+        Some((admin, PgStore { db }, schema))
+    }
+
+    #[tokio::test]
+    async fn adopts_a_hand_applied_first_migration() {
+        let Some((admin, store, schema)) = scratch_schema().await else {
+            return;
+        };
+
+        // The database as it was set up by hand: 001 only, with data
+        store.db.execute(include_str!("../../migrations/001_create_prompts.sql")).await.unwrap();
+        sqlx::query("INSERT INTO prompts (title, content) VALUES ('Greeting', 'Hello')")
+            .execute(&store.db)
+            .await
+            .unwrap();
+
+        prepare_schema(&store, true).await.unwrap();
+
+        let status = store.schema_status().await.unwrap();
+        assert!(status.pending.is_empty() && status.unknown.is_empty(), "{status:?}");
+        assert_eq!(status.applied.len(), MIGRATOR.iter().count());
+        let (title, version): (String, i32) = sqlx::query_as("SELECT title, version FROM prompts")
+            .fetch_one(&store.db)
+            .await
+            .unwrap();
+        assert_eq!((title.as_str(), version), ("Greeting", 1));
+
+        // Only a database without history is adopted
+        assert!(!store.adopt_baseline().await.unwrap());
+
+        store.db.close().await;
+        admin.execute(&*format!("DROP SCHEMA {schema} CASCADE")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_starved_pool_reports_a_pool_timeout() {
+        let Some((admin, store, schema)) = scratch_schema().await else {
+            return;
+        };
+        let db = PgPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_with(store.db.connect_options().as_ref().clone())
+            .await
+            .unwrap();
+        let starved = PgStore { db };
+
+        let held = starved.db.acquire().await.unwrap();
+        let e = starved.ping().await.unwrap_err();
+        assert!(e.is_pool_timeout(), "{e}");
+        drop(held);
+        starved.ping().await.unwrap();
+
+        starved.db.close().await;
+        store.db.close().await;
+        admin.execute(&*format!("DROP SCHEMA {schema} CASCADE")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_creates_get_distinct_slugs() {
+        let Some((admin, store, schema)) = scratch_schema().await else {
+            return;
+        };
+        prepare_schema(&store, true).await.unwrap();
+        let user = store.create_user("ada@example.com", "not-a-hash").await.unwrap();
+        let workspace = store.create_workspace("Team", user.id).await.unwrap();
+
+        let mut creates = tokio::task::JoinSet::new();
+        for _ in 0..8 {
+            let store = PgStore { db: store.db.clone() };
+            creates.spawn(async move {
+                let new = NewPrompt {
+                    workspace_id: workspace.id,
+                    owner_id: user.id,
+                    slug: None,
+                    title: "Greeting".to_string(),
+                    content: "Hello".to_string(),
+                    tags: vec![],
+                };
+                store.create_prompt(new).await.unwrap().slug
+            });
+        }
+        let mut slugs = creates.join_all().await;
+        slugs.sort();
+        slugs.dedup();
+        assert_eq!(slugs.len(), 8, "{slugs:?}");
+
+        store.db.close().await;
+        admin.execute(&*format!("DROP SCHEMA {schema} CASCADE")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn search_highlights_are_escaped() {
+        let Some((admin, store, schema)) = scratch_schema().await else {
+            return;
+        };
+        prepare_schema(&store, true).await.unwrap();
+        let user = store.create_user("ada@example.com", "not-a-hash").await.unwrap();
+        let workspace = store.create_workspace("Team", user.id).await.unwrap();
+        let new = NewPrompt {
+            workspace_id: workspace.id,
+            owner_id: user.id,
+            slug: None,
+            title: "<b>Alert</b> & co".to_string(),
+            content: r#"Reply to <img src=x onerror="alert(1)"> with an alert & a < b note"#.to_string(),
+            tags: vec![],
+        };
+        store.create_prompt(new).await.unwrap();
+
+        let hits = store.search_prompts(workspace.id, &search::parse("alert"), 10).await.unwrap();
+        assert_eq!(hits[0].title_highlight, "&lt;b&gt;<mark>Alert</mark>&lt;/b&gt; &amp; co");
+        // ts_headline leaves tags out of fragments but passes `&` and `<` through
+        let snippet = &hits[0].snippet;
+        assert!(snippet.ends_with("with an <mark>alert</mark> &amp; a &lt; b note"), "{snippet}");
+        assert!(!snippet.contains("<img"), "{snippet}");
+
+        store.db.close().await;
+        admin.execute(&*format!("DROP SCHEMA {schema} CASCADE")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn leaves_an_empty_database_to_the_migrator() {
+        let Some((admin, store, schema)) = scratch_schema().await else {
+            return;
+        };
+
+        assert!(!store.adopt_baseline().await.unwrap());
+        prepare_schema(&store, true).await.unwrap();
+        assert!(store.schema_status().await.unwrap().pending.is_empty());
+
+        store.db.close().await;
+        admin.execute(&*format!("DROP SCHEMA {schema} CASCADE")).await.unwrap();
+    }
+}
@@ -0,0 +1,949 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::types::Json;
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool, Transaction};
+use uuid::Uuid;
+
+use super::{
+    AccountStore, ApiKeyGrant, NewApiKey, NewPrompt, PoolStats, PromptChanges, PromptCounts,
+    PromptQuery, PromptStore, SchemaStatus, SchemaStore, SortField, SortOrder, StoreError,
+    StoreResult, TagMatch, EMAIL_TAKEN, LAST_OWNER, SLUG_TAKEN, now,
+};
+use crate::config::DatabaseConfig;
+use crate::models::{
+    ApiKey, LabelMove, Member, Prompt, PromptLabel, PromptVersion, SchemaMigration, SearchHit,
+    TagCount, User, Workspace, WorkspaceMembership,
+};
+use crate::pagination::{Cursor, CursorKey};
+use crate::search::{self, Term};
+use crate::slug;
+use crate::workspace::Role;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+// Ids and timestamps are generated here rather than by the database, the same
+// way the in-memory store does it, so all backends hand out identical values.
+pub struct SqliteStore {
+    db: SqlitePool,
+}
+
+impl SqliteStore {
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(&config.url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+        // A single connection serializes access. SQLite allows only one writer
+        // anyway, and a statement left open on another pooled connection would
+        // pin it to a stale snapshot.
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .connect_with(options)
+            .await?;
+        Ok(SqliteStore { db })
+    }
+}
+
+// SQLite compares timestamps as text, so they are always written with the same
+// width and offset for ordering and keyset comparisons to hold
+fn ts(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+// SQLite has no array type; scopes are kept as a JSON array
+#[derive(FromRow)]
+struct ApiKeyRow {
+    id: Uuid,
+    name: String,
+    prefix: String,
+    scopes: Json<Vec<String>>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKeyRow> for ApiKey {
+    fn from(row: ApiKeyRow) -> Self {
+        ApiKey {
+            id: row.id,
+            name: row.name,
+            prefix: row.prefix,
+            scopes: row.scopes.0,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[async_trait]
+impl PromptStore for SqliteStore {
+    async fn create_prompt(&self, new: NewPrompt) -> StoreResult<Prompt> {
+        let mut tx = self.db.begin().await?;
+
+        let slug = match new.slug {
+            Some(slug) => slug,
+            None => free_slug(&mut tx, new.workspace_id, &slug::slugify(&new.title)).await?,
+        };
+        let created_at = ts(now());
+        let mut prompt = sqlx::query_as::<_, Prompt>(
+            "INSERT INTO prompts \
+             (id, workspace_id, slug, title, content, owner_id, version, revision, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, 1, 1, ?, ?) RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(new.workspace_id)
+        .bind(&slug)
+        .bind(&new.title)
+        .bind(&new.content)
+        .bind(new.owner_id)
+        .bind(&created_at)
+        .bind(&created_at)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(slug_conflict)?;
+
+        claim_slug(&mut tx, new.workspace_id, &slug).await?;
+
+        record_version(&mut tx, &prompt).await?;
+        set_tags(&mut tx, prompt.id, &new.tags).await?;
+        tx.commit().await?;
+
+        prompt.tags = new.tags;
+        Ok(prompt)
+    }
+
+    async fn list_prompts(&self, workspace_id: Uuid, params: &PromptQuery) -> StoreResult<Vec<Prompt>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM prompts WHERE deleted_at IS NULL AND workspace_id = ");
+        query.push_bind(workspace_id);
+
+        if !params.tags.is_empty() {
+            query
+                .push(" AND id IN (SELECT pt.prompt_id FROM prompt_tags pt \
+                       JOIN tags t ON t.id = pt.tag_id WHERE t.name IN (SELECT value FROM json_each(")
+                .push_bind(Json(&params.tags))
+                .push("))");
+            if params.tag_match == TagMatch::All {
+                query
+                    .push(" GROUP BY pt.prompt_id HAVING COUNT(*) = ")
+                    .push_bind(params.tags.len() as i64);
+            }
+            query.push(")");
+        }
+        if let Some(after) = params.created_after {
+            query.push(" AND created_at > ").push_bind(ts(after));
+        }
+        if let Some(before) = params.created_before {
+            query.push(" AND created_at < ").push_bind(ts(before));
+        }
+
+        let comparison = match params.order {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+        match &params.after {
+            None => {}
+            Some(Cursor { key: CursorKey::CreatedAt(at), id }) => {
+                query
+                    .push(format_args!(" AND (created_at, id) {comparison} ("))
+                    .push_bind(ts(*at))
+                    .push(", ")
+                    .push_bind(*id)
+                    .push(")");
+            }
+            Some(Cursor { key: CursorKey::Title(title), id }) => {
+                query
+                    .push(format_args!(" AND (title, id) {comparison} ("))
+                    .push_bind(title)
+                    .push(", ")
+                    .push_bind(*id)
+                    .push(")");
+            }
+        }
+
+        let column = match params.sort {
+            SortField::CreatedAt => "created_at",
+            SortField::Title => "title",
+        };
+        let direction = match params.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        query
+            .push(format_args!(" ORDER BY {column} {direction}, id {direction} LIMIT "))
+            .push_bind(params.limit);
+
+        let mut prompts = query.build_query_as::<Prompt>().fetch_all(&self.db).await?;
+        attach_tags(&self.db, &mut prompts).await?;
+        Ok(prompts)
+    }
+
+    async fn search_prompts(
+        &self,
+        workspace_id: Uuid,
+        terms: &[Term],
+        limit: i64,
+    ) -> StoreResult<Vec<SearchHit>> {
+        // bm25() is lower for better matches; titles weigh more, like the
+        // A/B weights on the Postgres search vector
+        let mut hits = sqlx::query_as::<_, SearchHit>(
+            "SELECT p.*, -bm25(prompts_fts, 10.0, 4.0) AS rank, \
+                 highlight(prompts_fts, 0, ?1, ?2) AS title_highlight, \
+                 snippet(prompts_fts, 1, ?1, ?2, '', 30) AS snippet \
+             FROM prompts_fts \
+             JOIN prompt_search_keys k ON k.seq = prompts_fts.rowid \
+             JOIN prompts p ON p.id = k.prompt_id \
+             WHERE prompts_fts MATCH ?3 AND p.workspace_id = ?4 AND p.deleted_at IS NULL \
+             ORDER BY bm25(prompts_fts, 10.0, 4.0), p.created_at DESC \
+             LIMIT ?5"
+        )
+        .bind(search::MATCH_START)
+        .bind(search::MATCH_END)
+        .bind(search::to_fts5(terms))
+        .bind(workspace_id)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        for hit in &mut hits {
+            hit.title_highlight = search::mark_matches(&hit.title_highlight);
+            hit.snippet = search::mark_matches(&hit.snippet);
+        }
+        attach_tags(&self.db, hits.iter_mut().map(|hit| &mut hit.prompt)).await?;
+        Ok(hits)
+    }
+
+    async fn get_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "SELECT * FROM prompts WHERE id = ? AND workspace_id = ? AND deleted_at IS NULL"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(mut prompt) = prompt else {
+            return Ok(None);
+        };
+        attach_tags(&self.db, [&mut prompt]).await?;
+        Ok(Some(prompt))
+    }
+
+    async fn get_prompt_by_slug(&self, workspace_id: Uuid, slug: &str) -> StoreResult<Option<Prompt>> {
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "SELECT * FROM prompts WHERE workspace_id = ?1 AND deleted_at IS NULL AND (slug = ?2 \
+             OR id = (SELECT prompt_id FROM prompt_slug_redirects WHERE workspace_id = ?1 AND slug = ?2))"
+        )
+        .bind(workspace_id)
+        .bind(slug)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(mut prompt) = prompt else {
+            return Ok(None);
+        };
+        attach_tags(&self.db, [&mut prompt]).await?;
+        Ok(Some(prompt))
+    }
+
+    async fn update_prompt(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        changes: PromptChanges,
+        expected: Option<&[i64]>,
+    ) -> StoreResult<Option<Prompt>> {
+        let mut tx = self.db.begin().await?;
+
+        let current = sqlx::query_as::<_, (String, String, String)>(
+            "SELECT slug, title, content FROM prompts WHERE id = ? AND workspace_id = ?"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let content_changed = current
+            .as_ref()
+            .is_some_and(|(_, title, content)| *title != changes.title || *content != changes.content);
+        let old_slug = current.filter(|_| changes.slug.is_some()).map(|(slug, _, _)| slug);
+
+        let updated_at = ts(now());
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "UPDATE prompts SET title = ?1, content = ?2, slug = COALESCE(?3, slug), \
+             version = version + ?8, revision = revision + 1, updated_at = ?4 \
+             WHERE id = ?5 AND workspace_id = ?6 AND deleted_at IS NULL \
+             AND (?7 IS NULL OR revision IN (SELECT value FROM json_each(?7))) \
+             RETURNING *"
+        )
+        .bind(&changes.title)
+        .bind(&changes.content)
+        .bind(&changes.slug)
+        .bind(&updated_at)
+        .bind(id)
+        .bind(workspace_id)
+        .bind(expected.map(Json))
+        .bind(i32::from(content_changed))
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(slug_conflict)?;
+
+        let Some(mut prompt) = prompt else {
+            return missing_or_stale(&mut *tx, workspace_id, id, expected).await.map(|()| None);
+        };
+
+        if let Some(old_slug) = old_slug.filter(|old| *old != prompt.slug) {
+            claim_slug(&mut tx, workspace_id, &prompt.slug).await?;
+            sqlx::query(
+                "INSERT INTO prompt_slug_redirects (workspace_id, slug, prompt_id, created_at) \
+                 VALUES (?, ?, ?, ?) \
+                 ON CONFLICT (workspace_id, slug) DO UPDATE \
+                 SET prompt_id = excluded.prompt_id, created_at = excluded.created_at"
+            )
+            .bind(workspace_id)
+            .bind(&old_slug)
+            .bind(id)
+            .bind(&updated_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if content_changed {
+            record_version(&mut tx, &prompt).await?;
+        }
+        match changes.tags {
+            Some(tags) => {
+                set_tags(&mut tx, prompt.id, &tags).await?;
+                prompt.tags = tags;
+            }
+            None => attach_tags(&mut *tx, [&mut prompt]).await?,
+        }
+        tx.commit().await?;
+
+        Ok(Some(prompt))
+    }
+
+    async fn delete_prompt(&self, workspace_id: Uuid, id: Uuid, expected: Option<&[i64]>) -> StoreResult<bool> {
+        let result = sqlx::query(
+            "UPDATE prompts SET deleted_at = ?, revision = revision + 1 \
+             WHERE id = ? AND workspace_id = ? AND deleted_at IS NULL \
+             AND (?4 IS NULL OR revision IN (SELECT value FROM json_each(?4)))"
+        )
+        .bind(ts(now()))
+        .bind(id)
+        .bind(workspace_id)
+        .bind(expected.map(Json))
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            return Ok(true);
+        }
+        missing_or_stale(&self.db, workspace_id, id, expected).await.map(|()| false)
+    }
+
+    async fn list_trash(&self, workspace_id: Uuid) -> StoreResult<Vec<Prompt>> {
+        let mut prompts = sqlx::query_as::<_, Prompt>(
+            "SELECT * FROM prompts WHERE workspace_id = ? AND deleted_at IS NOT NULL \
+             ORDER BY deleted_at DESC, id"
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        attach_tags(&self.db, &mut prompts).await?;
+        Ok(prompts)
+    }
+
+    async fn restore_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        let prompt = sqlx::query_as::<_, Prompt>(
+            "UPDATE prompts SET deleted_at = NULL, revision = revision + 1 \
+             WHERE id = ? AND workspace_id = ? AND deleted_at IS NOT NULL RETURNING *"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(mut prompt) = prompt else {
+            return Ok(None);
+        };
+        attach_tags(&self.db, [&mut prompt]).await?;
+        Ok(Some(prompt))
+    }
+
+    async fn purge_deleted(&self, cutoff: DateTime<Utc>) -> StoreResult<u64> {
+        let result = sqlx::query("DELETE FROM prompts WHERE deleted_at < ?")
+            .bind(ts(cutoff))
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn count_prompts(&self) -> StoreResult<PromptCounts> {
+        let (live, trashed) = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*) - COUNT(deleted_at), COUNT(deleted_at) FROM prompts"
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(PromptCounts { live, trashed })
+    }
+
+    async fn list_versions(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptVersion>> {
+        let versions = sqlx::query_as::<_, PromptVersion>(
+            "SELECT v.* FROM prompt_versions v JOIN prompts p ON p.id = v.prompt_id \
+             WHERE v.prompt_id = ? AND p.workspace_id = ? AND p.deleted_at IS NULL \
+             ORDER BY v.version"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(versions)
+    }
+
+    async fn get_version(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        version: i32,
+    ) -> StoreResult<Option<PromptVersion>> {
+        let version = sqlx::query_as::<_, PromptVersion>(
+            "SELECT v.* FROM prompt_versions v JOIN prompts p ON p.id = v.prompt_id \
+             WHERE v.prompt_id = ? AND v.version = ? AND p.workspace_id = ? \
+             AND p.deleted_at IS NULL"
+        )
+        .bind(id)
+        .bind(version)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(version)
+    }
+
+    async fn list_tags(&self, workspace_id: Uuid) -> StoreResult<Vec<TagCount>> {
+        let tags = sqlx::query_as::<_, TagCount>(
+            "SELECT t.name, COUNT(*) AS count FROM tags t \
+             JOIN prompt_tags pt ON pt.tag_id = t.id \
+             JOIN prompts p ON p.id = pt.prompt_id \
+             WHERE p.workspace_id = ? AND p.deleted_at IS NULL \
+             GROUP BY t.name ORDER BY t.name"
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(tags)
+    }
+
+    async fn list_labels(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptLabel>> {
+        let labels = sqlx::query_as::<_, PromptLabel>(
+            "SELECT l.* FROM prompt_labels l JOIN prompts p ON p.id = l.prompt_id \
+             WHERE l.prompt_id = ? AND p.workspace_id = ? AND p.deleted_at IS NULL \
+             ORDER BY l.name"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(labels)
+    }
+
+    async fn get_label(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Option<PromptLabel>> {
+        let label = sqlx::query_as::<_, PromptLabel>(
+            "SELECT l.* FROM prompt_labels l JOIN prompts p ON p.id = l.prompt_id \
+             WHERE l.prompt_id = ? AND l.name = ? AND p.workspace_id = ? AND p.deleted_at IS NULL"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(workspace_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(label)
+    }
+
+    async fn move_label(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        name: &str,
+        version: i32,
+        moved_by: Uuid,
+    ) -> StoreResult<Option<PromptLabel>> {
+        let mut tx = self.db.begin().await?;
+
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM prompt_versions v JOIN prompts p ON p.id = v.prompt_id \
+             WHERE p.id = ? AND p.workspace_id = ? AND p.deleted_at IS NULL AND v.version = ?)"
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .bind(version)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !exists {
+            return Ok(None);
+        }
+
+        let current = sqlx::query_as::<_, PromptLabel>(
+            "SELECT * FROM prompt_labels WHERE prompt_id = ? AND name = ?"
+        )
+        .bind(id)
+        .bind(name)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(label) = current.as_ref().filter(|label| label.version == version) {
+            return Ok(Some(label.clone()));
+        }
+
+        let moved_at = ts(now());
+        let label = sqlx::query_as::<_, PromptLabel>(
+            "INSERT INTO prompt_labels (prompt_id, name, version, updated_by, updated_at) \
+             VALUES (?, ?, ?, ?, ?) \
+             ON CONFLICT (prompt_id, name) DO UPDATE \
+             SET version = excluded.version, updated_by = excluded.updated_by, updated_at = excluded.updated_at \
+             RETURNING name, version, updated_by, updated_at"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(version)
+        .bind(moved_by)
+        .bind(&moved_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO prompt_label_moves (prompt_id, name, from_version, to_version, moved_by, moved_at) \
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(current.map(|label| label.version))
+        .bind(version)
+        .bind(moved_by)
+        .bind(&moved_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(label))
+    }
+
+    async fn label_history(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Vec<LabelMove>> {
+        let moves = sqlx::query_as::<_, LabelMove>(
+            "SELECT m.name, m.from_version, m.to_version, m.moved_by, u.email AS moved_by_email, m.moved_at \
+             FROM prompt_label_moves m \
+             JOIN prompts p ON p.id = m.prompt_id \
+             LEFT JOIN users u ON u.id = m.moved_by \
+             WHERE m.prompt_id = ? AND m.name = ? AND p.workspace_id = ? AND p.deleted_at IS NULL \
+             ORDER BY m.id"
+        )
+        .bind(id)
+        .bind(name)
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(moves)
+    }
+}
+
+#[async_trait]
+impl AccountStore for SqliteStore {
+    async fn create_user(&self, email: &str, password_hash: &str) -> StoreResult<User> {
+        sqlx::query_as::<_, User>(
+            "INSERT INTO users (id, email, password_hash, created_at) VALUES (?, ?, ?, ?) RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(email)
+        .bind(password_hash)
+        .bind(ts(now()))
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                StoreError::Conflict(EMAIL_TAKEN)
+            }
+            e => e.into(),
+        })
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>> {
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ?")
+            .bind(email)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn create_api_key(&self, key: NewApiKey) -> StoreResult<ApiKey> {
+        let api_key = sqlx::query_as::<_, ApiKeyRow>(
+            "INSERT INTO api_keys (id, user_id, name, prefix, key_hash, scopes, created_at, expires_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(key.user_id)
+        .bind(&key.name)
+        .bind(&key.prefix)
+        .bind(&key.key_hash)
+        .bind(Json(&key.scopes))
+        .bind(ts(now()))
+        .bind(key.expires_at.map(ts))
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(api_key.into())
+    }
+
+    async fn list_api_keys(&self, user_id: Uuid) -> StoreResult<Vec<ApiKey>> {
+        let api_keys = sqlx::query_as::<_, ApiKeyRow>(
+            "SELECT * FROM api_keys WHERE user_id = ? ORDER BY created_at"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(api_keys.into_iter().map(ApiKey::from).collect())
+    }
+
+    async fn delete_api_key(&self, user_id: Uuid, id: Uuid) -> StoreResult<bool> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn use_api_key(&self, key_hash: &str) -> StoreResult<Option<ApiKeyGrant>> {
+        let now = ts(now());
+        let grant = sqlx::query_as::<_, (Uuid, Json<Vec<String>>)>(
+            "UPDATE api_keys SET last_used_at = ?1 \
+             WHERE key_hash = ?2 AND (expires_at IS NULL OR expires_at > ?1) \
+             RETURNING user_id, scopes"
+        )
+        .bind(&now)
+        .bind(key_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(grant.map(|(user_id, Json(scopes))| ApiKeyGrant { user_id, scopes }))
+    }
+
+    async fn create_workspace(&self, name: &str, owner_id: Uuid) -> StoreResult<Workspace> {
+        let mut tx = self.db.begin().await?;
+        let created_at = ts(now());
+
+        let workspace = sqlx::query_as::<_, Workspace>(
+            "INSERT INTO workspaces (id, name, created_at) VALUES (?, ?, ?) RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(name)
+        .bind(&created_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?)"
+        )
+        .bind(workspace.id)
+        .bind(owner_id)
+        .bind(Role::Owner)
+        .bind(&created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(workspace)
+    }
+
+    async fn list_workspaces(&self, user_id: Uuid) -> StoreResult<Vec<WorkspaceMembership>> {
+        let workspaces = sqlx::query_as::<_, WorkspaceMembership>(
+            "SELECT w.*, m.role FROM workspaces w \
+             JOIN workspace_members m ON m.workspace_id = w.id \
+             WHERE m.user_id = ? ORDER BY w.name"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(workspaces)
+    }
+
+    async fn member_role(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<Option<Role>> {
+        let role = sqlx::query_scalar::<_, Role>(
+            "SELECT role FROM workspace_members WHERE workspace_id = ? AND user_id = ?"
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(role)
+    }
+
+    async fn list_members(&self, workspace_id: Uuid) -> StoreResult<Vec<Member>> {
+        let members = sqlx::query_as::<_, Member>(
+            "SELECT m.user_id, u.email, m.role, m.created_at FROM workspace_members m \
+             JOIN users u ON u.id = m.user_id \
+             WHERE m.workspace_id = ? ORDER BY u.email"
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(members)
+    }
+
+    async fn put_member(&self, workspace_id: Uuid, email: &str, role: Role) -> StoreResult<Option<Member>> {
+        let mut tx = self.db.begin().await?;
+
+        // SQLite doesn't allow writes inside a CTE, so upsert and read back separately
+        let user_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM users WHERE email = ?")
+            .bind(email)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        sqlx::query(
+            "INSERT INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?) \
+             ON CONFLICT (workspace_id, user_id) DO UPDATE SET role = excluded.role"
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(role)
+        .bind(ts(now()))
+        .execute(&mut *tx)
+        .await?;
+
+        let member = sqlx::query_as::<_, Member>(
+            "SELECT m.user_id, u.email, m.role, m.created_at FROM workspace_members m \
+             JOIN users u ON u.id = m.user_id \
+             WHERE m.workspace_id = ? AND m.user_id = ?"
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        ensure_owner_remains(&mut tx, workspace_id).await?;
+        tx.commit().await?;
+        Ok(Some(member))
+    }
+
+    async fn remove_member(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<bool> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query("DELETE FROM workspace_members WHERE workspace_id = ? AND user_id = ?")
+            .bind(workspace_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        ensure_owner_remains(&mut tx, workspace_id).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl SchemaStore for SqliteStore {
+    async fn schema_status(&self) -> StoreResult<SchemaStatus> {
+        let mut conn = self.db.acquire().await?;
+        conn.ensure_migrations_table().await?;
+
+        let applied = sqlx::query_as::<_, SchemaMigration>(
+            "SELECT version, description, installed_on FROM _sqlx_migrations \
+             WHERE success ORDER BY version"
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(SchemaStatus::new(&MIGRATOR, applied))
+    }
+
+    async fn migrate(&self) -> StoreResult<()> {
+        MIGRATOR.run(&self.db).await?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        sqlx::query("SELECT 1").execute(&self.db).await?;
+        Ok(())
+    }
+
+    async fn close(&self) {
+        self.db.close().await;
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats::of(&self.db))
+    }
+}
+
+// Snapshots the prompt's current title and content as an immutable version row
+async fn record_version(tx: &mut Transaction<'_, Sqlite>, prompt: &Prompt) -> StoreResult<()> {
+    sqlx::query(
+        "INSERT INTO prompt_versions (prompt_id, version, title, content, created_at) \
+         VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(prompt.id)
+    .bind(prompt.version)
+    .bind(&prompt.title)
+    .bind(&prompt.content)
+    // The write that made this version, like `NOW()` in a Postgres transaction
+    .bind(ts(prompt.updated_at))
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Replaces the prompt's tags, creating any tags that don't exist yet
+async fn set_tags(tx: &mut Transaction<'_, Sqlite>, prompt_id: Uuid, tags: &[String]) -> StoreResult<()> {
+    for tag in tags {
+        sqlx::query("INSERT INTO tags (id, name, created_at) VALUES (?, ?, ?) ON CONFLICT (name) DO NOTHING")
+            .bind(Uuid::new_v4())
+            .bind(tag)
+            .bind(ts(now()))
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    sqlx::query("DELETE FROM prompt_tags WHERE prompt_id = ?")
+        .bind(prompt_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO prompt_tags (prompt_id, tag_id) \
+         SELECT ?, id FROM tags WHERE name IN (SELECT value FROM json_each(?))"
+    )
+    .bind(prompt_id)
+    .bind(Json(tags))
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Loads tags for a batch of prompts in a single query
+async fn attach_tags<'a, 'e, E>(
+    executor: E,
+    prompts: impl IntoIterator<Item = &'a mut Prompt>,
+) -> StoreResult<()>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let prompts: Vec<&mut Prompt> = prompts.into_iter().collect();
+    if prompts.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = QueryBuilder::<Sqlite>::new(
+        "SELECT pt.prompt_id, t.name FROM prompt_tags pt \
+         JOIN tags t ON t.id = pt.tag_id WHERE pt.prompt_id IN ("
+    );
+    let mut ids = query.separated(", ");
+    for prompt in &prompts {
+        ids.push_bind(prompt.id);
+    }
+    query.push(") ORDER BY t.name");
+    let rows = query.build_query_as::<(Uuid, String)>().fetch_all(executor).await?;
+
+    let mut by_prompt: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (prompt_id, name) in rows {
+        by_prompt.entry(prompt_id).or_default().push(name);
+    }
+    for prompt in prompts {
+        prompt.tags = by_prompt.remove(&prompt.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+// The first of `base`, `base-2`, ... that no prompt uses or used to use
+async fn free_slug(tx: &mut Transaction<'_, Sqlite>, workspace_id: Uuid, base: &str) -> StoreResult<String> {
+    let taken = sqlx::query_scalar::<_, String>(
+        "SELECT slug FROM prompts WHERE workspace_id = ?1 AND (slug = ?2 OR slug LIKE ?3) \
+         UNION ALL \
+         SELECT slug FROM prompt_slug_redirects WHERE workspace_id = ?1 AND (slug = ?2 OR slug LIKE ?3)"
+    )
+    .bind(workspace_id)
+    .bind(base)
+    .bind(format!("{base}-%"))
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(slug::first_free(base, &taken))
+}
+
+// A slug taken by a prompt no longer redirects to whoever had it before
+async fn claim_slug(tx: &mut Transaction<'_, Sqlite>, workspace_id: Uuid, slug: &str) -> StoreResult<()> {
+    sqlx::query("DELETE FROM prompt_slug_redirects WHERE workspace_id = ? AND slug = ?")
+        .bind(workspace_id)
+        .bind(slug)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+// Slugs are the only unique column a prompt write can collide on
+fn slug_conflict(e: sqlx::Error) -> StoreError {
+    match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => StoreError::Conflict(SLUG_TAKEN),
+        e => e.into(),
+    }
+}
+
+// Tells apart why a revision-guarded write matched no row
+async fn missing_or_stale<'e, E>(executor: E, workspace_id: Uuid, id: Uuid, expected: Option<&[i64]>) -> StoreResult<()>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    if expected.is_none() {
+        return Ok(());
+    }
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM prompts WHERE id = ? AND workspace_id = ? AND deleted_at IS NULL)"
+    )
+    .bind(id)
+    .bind(workspace_id)
+    .fetch_one(executor)
+    .await?;
+
+    if exists {
+        return Err(StoreError::Stale);
+    }
+    Ok(())
+}
+
+// Membership changes must never leave a workspace without an owner
+async fn ensure_owner_remains(tx: &mut Transaction<'_, Sqlite>, workspace_id: Uuid) -> StoreResult<()> {
+    let owners = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM workspace_members WHERE workspace_id = ? AND role = 'owner'"
+    )
+    .bind(workspace_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if owners == 0 {
+        return Err(StoreError::Conflict(LAST_OWNER));
+    }
+    Ok(())
+}
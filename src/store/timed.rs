@@ -0,0 +1,214 @@
+use std::future::Future;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::{
+    AccountStore, ApiKeyGrant, NewApiKey, NewPrompt, PoolStats, PromptChanges, PromptCounts,
+    PromptQuery, PromptStore, SchemaStatus, SchemaStore, StoreError, StoreResult,
+};
+use crate::models::{
+    ApiKey, LabelMove, Member, Prompt, PromptLabel, PromptVersion, SearchHit, TagCount, User,
+    Workspace, WorkspaceMembership,
+};
+use crate::search::Term;
+use crate::telemetry;
+use crate::workspace::Role;
+
+// Wraps a backend to time every call. Each call runs in its own span, which
+// also holds sqlx's statement events, is logged at debug level and adds to
+// the request's `db_ms`.
+pub struct Timed<S>(pub S);
+
+async fn timed<T>(op: &'static str, call: impl Future<Output = StoreResult<T>>) -> StoreResult<T> {
+    let span = tracing::info_span!("store", op, otel.name = op, otel.kind = "client");
+    let started = Instant::now();
+    let result = call.instrument(span.clone()).await;
+    let elapsed = started.elapsed();
+
+    telemetry::record_store_time(elapsed);
+    if result.as_ref().is_err_and(StoreError::is_pool_timeout) {
+        telemetry::record_pool_timeout();
+    }
+    tracing::debug!(parent: &span, elapsed_ms = telemetry::millis(elapsed), ok = result.is_ok(), "store call");
+    result
+}
+
+#[async_trait]
+impl<S: PromptStore> PromptStore for Timed<S> {
+    async fn create_prompt(&self, prompt: NewPrompt) -> StoreResult<Prompt> {
+        timed("create_prompt", self.0.create_prompt(prompt)).await
+    }
+
+    async fn list_prompts(&self, workspace_id: Uuid, query: &PromptQuery) -> StoreResult<Vec<Prompt>> {
+        timed("list_prompts", self.0.list_prompts(workspace_id, query)).await
+    }
+
+    async fn search_prompts(
+        &self,
+        workspace_id: Uuid,
+        terms: &[Term],
+        limit: i64,
+    ) -> StoreResult<Vec<SearchHit>> {
+        timed("search_prompts", self.0.search_prompts(workspace_id, terms, limit)).await
+    }
+
+    async fn get_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        timed("get_prompt", self.0.get_prompt(workspace_id, id)).await
+    }
+
+    async fn get_prompt_by_slug(&self, workspace_id: Uuid, slug: &str) -> StoreResult<Option<Prompt>> {
+        timed("get_prompt_by_slug", self.0.get_prompt_by_slug(workspace_id, slug)).await
+    }
+
+    async fn update_prompt(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        changes: PromptChanges,
+        expected: Option<&[i64]>,
+    ) -> StoreResult<Option<Prompt>> {
+        timed("update_prompt", self.0.update_prompt(workspace_id, id, changes, expected)).await
+    }
+
+    async fn delete_prompt(&self, workspace_id: Uuid, id: Uuid, expected: Option<&[i64]>) -> StoreResult<bool> {
+        timed("delete_prompt", self.0.delete_prompt(workspace_id, id, expected)).await
+    }
+
+    async fn list_trash(&self, workspace_id: Uuid) -> StoreResult<Vec<Prompt>> {
+        timed("list_trash", self.0.list_trash(workspace_id)).await
+    }
+
+    async fn restore_prompt(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Option<Prompt>> {
+        timed("restore_prompt", self.0.restore_prompt(workspace_id, id)).await
+    }
+
+    async fn purge_deleted(&self, cutoff: DateTime<Utc>) -> StoreResult<u64> {
+        timed("purge_deleted", self.0.purge_deleted(cutoff)).await
+    }
+
+    async fn count_prompts(&self) -> StoreResult<PromptCounts> {
+        timed("count_prompts", self.0.count_prompts()).await
+    }
+
+    async fn list_versions(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptVersion>> {
+        timed("list_versions", self.0.list_versions(workspace_id, id)).await
+    }
+
+    async fn get_version(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        version: i32,
+    ) -> StoreResult<Option<PromptVersion>> {
+        timed("get_version", self.0.get_version(workspace_id, id, version)).await
+    }
+
+    async fn list_tags(&self, workspace_id: Uuid) -> StoreResult<Vec<TagCount>> {
+        timed("list_tags", self.0.list_tags(workspace_id)).await
+    }
+
+    async fn list_labels(&self, workspace_id: Uuid, id: Uuid) -> StoreResult<Vec<PromptLabel>> {
+        timed("list_labels", self.0.list_labels(workspace_id, id)).await
+    }
+
+    async fn get_label(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Option<PromptLabel>> {
+        timed("get_label", self.0.get_label(workspace_id, id, name)).await
+    }
+
+    async fn move_label(
+        &self,
+        workspace_id: Uuid,
+        id: Uuid,
+        name: &str,
+        version: i32,
+        moved_by: Uuid,
+    ) -> StoreResult<Option<PromptLabel>> {
+        timed("move_label", self.0.move_label(workspace_id, id, name, version, moved_by)).await
+    }
+
+    async fn label_history(&self, workspace_id: Uuid, id: Uuid, name: &str) -> StoreResult<Vec<LabelMove>> {
+        timed("label_history", self.0.label_history(workspace_id, id, name)).await
+    }
+}
+
+#[async_trait]
+impl<S: AccountStore> AccountStore for Timed<S> {
+    async fn create_user(&self, email: &str, password_hash: &str) -> StoreResult<User> {
+        timed("create_user", self.0.create_user(email, password_hash)).await
+    }
+
+    async fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>> {
+        timed("find_user_by_email", self.0.find_user_by_email(email)).await
+    }
+
+    async fn create_api_key(&self, key: NewApiKey) -> StoreResult<ApiKey> {
+        timed("create_api_key", self.0.create_api_key(key)).await
+    }
+
+    async fn list_api_keys(&self, user_id: Uuid) -> StoreResult<Vec<ApiKey>> {
+        timed("list_api_keys", self.0.list_api_keys(user_id)).await
+    }
+
+    async fn delete_api_key(&self, user_id: Uuid, id: Uuid) -> StoreResult<bool> {
+        timed("delete_api_key", self.0.delete_api_key(user_id, id)).await
+    }
+
+    async fn use_api_key(&self, key_hash: &str) -> StoreResult<Option<ApiKeyGrant>> {
+        timed("use_api_key", self.0.use_api_key(key_hash)).await
+    }
+
+    async fn create_workspace(&self, name: &str, owner_id: Uuid) -> StoreResult<Workspace> {
+        timed("create_workspace", self.0.create_workspace(name, owner_id)).await
+    }
+
+    async fn list_workspaces(&self, user_id: Uuid) -> StoreResult<Vec<WorkspaceMembership>> {
+        timed("list_workspaces", self.0.list_workspaces(user_id)).await
+    }
+
+    async fn member_role(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<Option<Role>> {
+        timed("member_role", self.0.member_role(workspace_id, user_id)).await
+    }
+
+    async fn list_members(&self, workspace_id: Uuid) -> StoreResult<Vec<Member>> {
+        timed("list_members", self.0.list_members(workspace_id)).await
+    }
+
+    async fn put_member(&self, workspace_id: Uuid, email: &str, role: Role) -> StoreResult<Option<Member>> {
+        timed("put_member", self.0.put_member(workspace_id, email, role)).await
+    }
+
+    async fn remove_member(&self, workspace_id: Uuid, user_id: Uuid) -> StoreResult<bool> {
+        timed("remove_member", self.0.remove_member(workspace_id, user_id)).await
+    }
+}
+
+#[async_trait]
+impl<S: SchemaStore> SchemaStore for Timed<S> {
+    async fn schema_status(&self) -> StoreResult<SchemaStatus> {
+        timed("schema_status", self.0.schema_status()).await
+    }
+
+    async fn migrate(&self) -> StoreResult<()> {
+        timed("migrate", self.0.migrate()).await
+    }
+
+    async fn adopt_baseline(&self) -> StoreResult<bool> {
+        timed("adopt_baseline", self.0.adopt_baseline()).await
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        timed("ping", self.0.ping()).await
+    }
+
+    async fn close(&self) {
+        self.0.close().await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.0.pool_stats()
+    }
+}
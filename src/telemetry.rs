@@ -0,0 +1,257 @@
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle, PrometheusRecorder};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{LogConfig, LogFormat, OtelConfig, OtlpProtocol};
+use crate::request_id;
+use crate::store::{PoolStats, PromptCounts, PromptStore, StoreResult};
+
+const REQUESTS: &str = "http_requests_total";
+const REQUEST_DURATION: &str = "http_request_duration_seconds";
+const POOL_ACQUIRE_TIMEOUTS: &str = "db_pool_acquire_timeouts_total";
+
+// Requests that matched no route share one label value, so scanners probing
+// random paths can't blow up the number of series
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Counting prompts scans the whole table, so scrapes this soon after the
+// last count reuse it
+const PROMPT_COUNT_MAX_AGE: Duration = Duration::from_secs(60);
+
+tokio::task_local! {
+    // Time spent in the stores by the current request
+    static STORE_TIME: Cell<Duration>;
+}
+
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Keeps the trace exporter alive; `shutdown` flushes spans still buffered
+pub struct Telemetry {
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.tracer_provider {
+            if let Err(e) = provider.shutdown() {
+                tracing::warn!(error = %e, "flushing traces failed");
+            }
+        }
+    }
+}
+
+// Logs go to stdout; spans are also exported over OTLP when
+// `otel.endpoint` is set
+pub fn init(log: &LogConfig, otel: &OtelConfig) -> Result<Telemetry, Box<dyn std::error::Error>> {
+    let logs = match log.format {
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().flatten_event(true).boxed(),
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+    };
+
+    let tracer_provider = if otel.endpoint.is_empty() {
+        None
+    } else {
+        Some(tracer_provider(otel)?)
+    };
+    let traces = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+    });
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::new(&log.level))
+        .with(logs)
+        .with(traces)
+        .try_init()?;
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(Telemetry { tracer_provider })
+}
+
+pub fn tracer_provider(otel: &OtelConfig) -> Result<SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+    let protocol = match otel.protocol {
+        OtlpProtocol::Protobuf => Protocol::HttpBinary,
+        OtlpProtocol::Json => Protocol::HttpJson,
+    };
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", otel.endpoint.trim_end_matches('/')))
+        .with_protocol(protocol)
+        .with_timeout(EXPORT_TIMEOUT)
+        .build()?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(otel.service_name.clone()).build())
+        .build())
+}
+
+// The process-wide metrics recorder; render the handle to serve `/metrics`
+pub fn install_metrics() -> Result<PrometheusHandle, BuildError> {
+    let recorder = metrics_recorder()?;
+    let handle = recorder.handle();
+    metrics::set_global_recorder(recorder)?;
+    describe_metrics();
+    Ok(handle)
+}
+
+pub fn metrics_recorder() -> Result<PrometheusRecorder, BuildError> {
+    Ok(PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(REQUEST_DURATION.to_string()), LATENCY_BUCKETS)?
+        .build_recorder())
+}
+
+// Pool acquire waits aren't reported: sqlx doesn't expose them, so only the
+// acquires that time out are counted
+pub fn describe_metrics() {
+    metrics::describe_counter!(REQUESTS, "Requests handled, by method, route and status");
+    metrics::describe_histogram!(
+        REQUEST_DURATION,
+        metrics::Unit::Seconds,
+        "Time to produce a response, by method and route"
+    );
+    metrics::describe_gauge!(
+        "prompthub_prompts",
+        "Prompts across all workspaces, by state; counted at most once a minute"
+    );
+    metrics::describe_gauge!("db_pool_connections", "Database pool connections, by state");
+    metrics::describe_gauge!("db_pool_max_connections", "Size limit of the database pool");
+    metrics::describe_counter!(
+        POOL_ACQUIRE_TIMEOUTS,
+        "Store calls that failed waiting for a database pool connection"
+    );
+    // Exported from the start, so a rate over it works before the first timeout
+    metrics::counter!(POOL_ACQUIRE_TIMEOUTS).absolute(0);
+}
+
+// The count behind the `prompthub_prompts` gauge. Scrapes that arrive while
+// it is being refreshed wait for that count rather than starting their own.
+#[derive(Clone, Default)]
+pub struct PromptCountCache(Arc<tokio::sync::Mutex<Option<(Instant, PromptCounts)>>>);
+
+impl PromptCountCache {
+    pub async fn get(&self, store: &dyn PromptStore) -> StoreResult<PromptCounts> {
+        let mut cached = self.0.lock().await;
+        if let Some((counted_at, counts)) = *cached {
+            if counted_at.elapsed() < PROMPT_COUNT_MAX_AGE {
+                return Ok(counts);
+            }
+        }
+        let counts = store.count_prompts().await?;
+        *cached = Some((Instant::now(), counts));
+        Ok(counts)
+    }
+}
+
+// Gauges are sampled when scraped rather than kept up to date on every write
+pub fn record_gauges(prompts: PromptCounts, pool: Option<PoolStats>) {
+    metrics::gauge!("prompthub_prompts", "state" => "live").set(prompts.live as f64);
+    metrics::gauge!("prompthub_prompts", "state" => "trashed").set(prompts.trashed as f64);
+
+    if let Some(pool) = pool {
+        metrics::gauge!("db_pool_connections", "state" => "idle").set(pool.idle);
+        metrics::gauge!("db_pool_connections", "state" => "in_use").set(pool.in_use);
+        metrics::gauge!("db_pool_max_connections").set(pool.max);
+    }
+}
+
+// Runs each request in a span carrying its id and route, logs one line when
+// it completes and records it in the request metrics. Must sit inside
+// `request_id::middleware`.
+pub async fn trace_requests(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = request_id::current().as_deref(),
+        method = %method,
+        path = %request.uri().path(),
+        route = route.as_deref(),
+        otel.name = format!("{method} {}", route.as_deref().unwrap_or(UNMATCHED_ROUTE)),
+        otel.kind = "server",
+        http.response.status_code = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+    );
+    // Continue the caller's trace when it sent a `traceparent`
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    let _ = span.set_parent(parent);
+
+    let started = Instant::now();
+    let (response, store_time) = STORE_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let response = next.run(request).await;
+            (response, STORE_TIME.with(Cell::get))
+        })
+        .instrument(span.clone())
+        .await;
+
+    let elapsed = started.elapsed();
+    let status = response.status().as_u16();
+    span.record("http.response.status_code", status);
+    if response.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    tracing::info!(
+        parent: &span,
+        status,
+        latency_ms = millis(elapsed),
+        db_ms = millis(store_time),
+        "request completed"
+    );
+
+    let method = method.to_string();
+    let route = route.unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    metrics::counter!(REQUESTS, "method" => method.clone(), "route" => route.clone(), "status" => status.to_string())
+        .increment(1);
+    metrics::histogram!(REQUEST_DURATION, "method" => method, "route" => route).record(elapsed.as_secs_f64());
+    response
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+pub fn record_pool_timeout() {
+    metrics::counter!(POOL_ACQUIRE_TIMEOUTS).increment(1);
+}
+
+// Adds to the current request's `db_ms`; a no-op outside of a request
+pub fn record_store_time(elapsed: Duration) {
+    let _ = STORE_TIME.try_with(|total| total.set(total.get() + elapsed));
+}
+
+// Milliseconds with microsecond precision
+pub fn millis(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Serialize;
+
+// A piece of prompt content: literal text or a `{{variable}}` placeholder
+enum Segment<'a> {
+    Text(&'a str),
+    Var(&'a str),
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenderError {
+    pub missing: Vec<String>,
+    pub unused: Vec<String>,
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+// Anything between braces that isn't a valid name is kept as literal text
+fn parse(content: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        let end = start + 2 + len + 2;
+
+        if is_valid_name(name) {
+            if start > 0 {
+                segments.push(Segment::Text(&rest[..start]));
+            }
+            segments.push(Segment::Var(name));
+        } else {
+            segments.push(Segment::Text(&rest[..end]));
+        }
+        rest = &rest[end..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    segments
+}
+
+// Declared variables in order of first appearance
+pub fn variables(content: &str) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    parse(content)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Var(name) if seen.insert(name) => Some(name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn render(content: &str, values: &HashMap<String, String>) -> Result<String, RenderError> {
+    let declared = variables(content);
+
+    let missing: Vec<String> = declared
+        .iter()
+        .filter(|name| !values.contains_key(*name))
+        .cloned()
+        .collect();
+    let mut unused: Vec<String> = values
+        .keys()
+        .filter(|key| !declared.contains(key))
+        .cloned()
+        .collect();
+    unused.sort();
+
+    if !missing.is_empty() || !unused.is_empty() {
+        return Err(RenderError { missing, unused });
+    }
+
+    let mut rendered = String::with_capacity(content.len());
+    for segment in parse(content) {
+        match segment {
+            Segment::Text(text) => rendered.push_str(text),
+            Segment::Var(name) => rendered.push_str(&values[name]),
+        }
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn variables_in_order_of_first_appearance() {
+        let content = "Hi {{ name }}, meet {{friend}}. Bye {{name}}!";
+        assert_eq!(variables(content), ["name", "friend"]);
+    }
+
+    #[test]
+    fn invalid_and_unterminated_placeholders_stay_literal() {
+        for content in ["Hello {{name", "{{}} and {{ 1st }}", "{{two words}}", "no braces at all"] {
+            assert!(variables(content).is_empty(), "{content}");
+            assert_eq!(render(content, &HashMap::new()).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn render_substitutes_every_occurrence() {
+        let text = render("{{a}}{{b}} and {{ a }} {{user.first-name}}", &values(&[("a", "1"), ("b", "2"), ("user.first-name", "Ada")]));
+        assert_eq!(text.unwrap(), "12 and 1 Ada");
+    }
+
+    #[test]
+    fn values_are_not_reparsed() {
+        let text = render("{{a}}", &values(&[("a", "{{b}}")]));
+        assert_eq!(text.unwrap(), "{{b}}");
+    }
+
+    #[test]
+    fn render_reports_missing_and_unused_values() {
+        let e = render("{{a}} {{b}}", &values(&[("b", "x"), ("z", "y"), ("c", "w")])).unwrap_err();
+        assert_eq!(e.missing, ["a"]);
+        assert_eq!(e.unused, ["c", "z"]);
+    }
+}
@@ -0,0 +1,1188 @@
+// Handler-level tests: the full router, middleware included, on the
+// in-memory store
+use axum::{
+    body::{self, Body},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+};
+use metrics_exporter_prometheus::PrometheusRecorder;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use super::*;
+use crate::config::DatabaseConfig;
+
+const ADMIN_TOKEN: &str = "test-admin-token-of-thirty-two-chars";
+
+struct TestApp {
+    router: Router,
+    state: AppState,
+    // Not installed: tests that check metrics make it their thread's recorder
+    recorder: PrometheusRecorder,
+}
+
+struct Reply {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Value,
+}
+
+impl Reply {
+    fn code(&self) -> &str {
+        self.body["code"].as_str().unwrap_or_default()
+    }
+}
+
+impl TestApp {
+    async fn new(url: &str) -> Self {
+        Self::with_server(url, &ServerConfig::default()).await
+    }
+
+    async fn with_server(url: &str, server: &ServerConfig) -> Self {
+        let database = DatabaseConfig { url: url.to_string(), ..DatabaseConfig::default() };
+        let stores = store::open(&database).await.unwrap();
+        store::prepare_schema(stores.schema.as_ref(), true).await.unwrap();
+        let recorder = telemetry::metrics_recorder().unwrap();
+        let state = AppState {
+            prompts: stores.prompts,
+            accounts: stores.accounts,
+            schema: stores.schema,
+            tokens: Arc::new(TokenSigner::new("test-secret", chrono::Duration::hours(1))),
+            admin_token: Some(Arc::from(ADMIN_TOKEN)),
+            metrics: recorder.handle(),
+            prompt_counts: PromptCountCache::default(),
+        };
+        TestApp { router: app(state.clone(), server), state, recorder }
+    }
+
+    // A user with a session token, created directly so only the auth tests
+    // pay for password hashing
+    async fn user(&self, email: &str) -> (Uuid, String) {
+        let user = self.state.accounts.create_user(email, "not-a-hash").await.unwrap();
+        (user.id, self.state.tokens.issue(user.id).0)
+    }
+
+    async fn workspace(&self, token: &str) -> String {
+        let reply = self.send(Method::POST, "/workspaces", Some(token), Some(json!({ "name": "Team" }))).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+        reply.body["id"].as_str().unwrap().to_string()
+    }
+
+    async fn send(&self, method: Method, uri: &str, token: Option<&str>, body: Option<Value>) -> Reply {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        self.request(request.body(body).unwrap()).await
+    }
+
+    async fn request(&self, request: Request<Body>) -> Reply {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+        };
+        Reply { status, headers, body }
+    }
+}
+
+// Tests taking a `TestApp` run once per store: on memory, and on an
+// in-memory SQLite database when that backend is built in
+macro_rules! on_every_store {
+    ($($test:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[tokio::test]
+                async fn $test() {
+                    super::$test(super::TestApp::new("memory://").await).await
+                }
+            )*
+        }
+
+        #[cfg(feature = "sqlite")]
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $test() {
+                    super::$test(super::TestApp::new("sqlite::memory:").await).await
+                }
+            )*
+        }
+    };
+}
+
+on_every_store!(
+    register_login_and_bearer_tokens,
+    api_keys_are_limited_to_their_scopes,
+    workspaces_are_isolated,
+    roles_gate_writes_and_deletes,
+    prompt_crud_and_versions,
+    ids_and_timestamps_round_trip,
+    cursors_page_through_every_prompt_once,
+    only_title_and_content_changes_mint_versions,
+    search_ranks_and_highlights_matches,
+    search_highlights_escape_prompt_text,
+    slugs_find_prompts_and_old_slugs_redirect,
+    etags_guard_concurrent_writes,
+    etags_tell_prompts_apart_when_a_slug_moves,
+    labels_pin_versions_and_record_moves,
+    invalid_bodies_are_problem_details,
+    bad_paths_and_queries_are_problem_details,
+    tags_are_validated_on_every_write,
+    tags_are_normalized_and_counted,
+    metrics_count_requests_by_route,
+    schema_status_is_for_operators_only,
+);
+
+async fn register_login_and_bearer_tokens(app: TestApp) {
+    let credentials = json!({ "email": " Ada@Example.com ", "password": "correct horse" });
+
+    let reply = app.send(Method::POST, "/auth/register", None, Some(credentials.clone())).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    assert_eq!(reply.body["email"], "ada@example.com");
+    assert!(reply.body.get("password_hash").is_none());
+
+    let reply = app.send(Method::POST, "/auth/register", None, Some(credentials)).await;
+    assert_eq!(reply.status, StatusCode::CONFLICT);
+
+    let wrong = json!({ "email": "ada@example.com", "password": "wrong horse" });
+    let reply = app.send(Method::POST, "/auth/login", None, Some(wrong)).await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+
+    let right = json!({ "email": "ada@example.com", "password": "correct horse" });
+    let reply = app.send(Method::POST, "/auth/login", None, Some(right)).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    let token = reply.body["token"].as_str().unwrap().to_string();
+
+    let reply = app.send(Method::GET, "/workspaces", Some(&token), None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+
+    for token in [None, Some("garbage"), Some(&*format!("{token}x"))] {
+        let reply = app.send(Method::GET, "/workspaces", token, None).await;
+        assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(reply.headers[header::CONTENT_TYPE], "application/problem+json");
+        assert_eq!(reply.code(), "unauthorized");
+        assert!(reply.body["request_id"].is_string());
+    }
+}
+
+async fn api_keys_are_limited_to_their_scopes(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+
+    let reply = app
+        .send(Method::POST, "/api-keys", Some(&token), Some(json!({ "name": "ci", "scopes": ["prompts:read"] })))
+        .await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    let key = reply.body["key"].as_str().unwrap().to_string();
+
+    let reply = app.send(Method::GET, &prompts, Some(&key), None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+
+    let body = json!({ "title": "Greeting", "content": "Hello" });
+    let reply = app.send(Method::POST, &prompts, Some(&key), Some(body)).await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+    assert_eq!(reply.code(), "missing_scope");
+
+    // Account management only takes session tokens
+    let reply = app.send(Method::GET, "/api-keys", Some(&key), None).await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+
+    let reply = app.send(Method::GET, "/api-keys", Some(&token), None).await;
+    let id = reply.body[0]["id"].as_str().unwrap().to_string();
+    assert!(reply.body[0]["last_used_at"].is_string());
+    assert!(reply.body[0].get("key").is_none());
+
+    let reply = app.send(Method::DELETE, &format!("/api-keys/{id}"), Some(&token), None).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    let reply = app.send(Method::GET, &prompts, Some(&key), None).await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+}
+
+async fn workspaces_are_isolated(app: TestApp) {
+    let (_, ada) = app.user("ada@example.com").await;
+    let (_, bob) = app.user("bob@example.com").await;
+    let ada_ws = app.workspace(&ada).await;
+    let bob_ws = app.workspace(&bob).await;
+
+    let body = json!({ "title": "Private", "content": "Ada's eyes only" });
+    let reply = app.send(Method::POST, &format!("/workspaces/{ada_ws}/prompts"), Some(&ada), Some(body)).await;
+    let id = reply.body["id"].as_str().unwrap().to_string();
+
+    // Not a member: the workspace looks missing
+    let reply = app.send(Method::GET, &format!("/workspaces/{ada_ws}/prompts"), Some(&bob), None).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+    assert_eq!(reply.code(), "workspace_not_found");
+
+    // Ada's prompt id under Bob's workspace: the prompt looks missing
+    for method in [Method::GET, Method::DELETE] {
+        let reply = app.send(method, &format!("/workspaces/{bob_ws}/prompts/{id}"), Some(&bob), None).await;
+        assert_eq!(reply.status, StatusCode::NOT_FOUND);
+        assert_eq!(reply.code(), "prompt_not_found");
+    }
+    let reply = app.send(Method::GET, &format!("/workspaces/{bob_ws}/prompts"), Some(&bob), None).await;
+    assert_eq!(reply.body["items"], json!([]));
+
+    let reply = app.send(Method::GET, "/workspaces", Some(&bob), None).await;
+    assert_eq!(reply.body.as_array().unwrap().len(), 1);
+}
+
+async fn roles_gate_writes_and_deletes(app: TestApp) {
+    let (_, owner) = app.user("owner@example.com").await;
+    let (_, editor) = app.user("editor@example.com").await;
+    let (_, viewer) = app.user("viewer@example.com").await;
+    let ws = app.workspace(&owner).await;
+    let members = format!("/workspaces/{ws}/members");
+    for (email, role) in [("editor@example.com", "editor"), ("viewer@example.com", "viewer")] {
+        let reply = app.send(Method::PUT, &members, Some(&owner), Some(json!({ "email": email, "role": role }))).await;
+        assert_eq!(reply.status, StatusCode::OK);
+    }
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let body = json!({ "title": "Greeting", "content": "Hello" });
+
+    let reply = app.send(Method::POST, &prompts, Some(&viewer), Some(body.clone())).await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+    let reply = app.send(Method::POST, &prompts, Some(&editor), Some(body)).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    let prompt = format!("{prompts}/{}", reply.body["id"].as_str().unwrap());
+
+    let reply = app.send(Method::GET, &prompt, Some(&viewer), None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    let reply = app.send(Method::DELETE, &prompt, Some(&editor), None).await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+    let reply = app.send(Method::DELETE, &prompt, Some(&owner), None).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+
+    let reply = app.send(Method::PUT, &members, Some(&editor), Some(json!({ "email": "viewer@example.com", "role": "owner" }))).await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+    assert_eq!(reply.code(), "insufficient_role");
+}
+
+async fn prompt_crud_and_versions(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let token = Some(token.as_str());
+
+    let body = json!({ "title": " Summary ", "content": "Summarize {{doc}}", "tags": ["B", "a", "a "] });
+    let reply = app.send(Method::POST, &prompts, token, Some(body)).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    assert_eq!(reply.body["title"], "Summary");
+    assert_eq!(reply.body["slug"], "summary");
+    assert_eq!(reply.body["tags"], json!(["a", "b"]));
+    assert_eq!(reply.body["variables"], json!(["doc"]));
+    assert_eq!(reply.body["version"], 1);
+    let id = reply.body["id"].as_str().unwrap().to_string();
+    let prompt = format!("{prompts}/{id}");
+
+    let reply = app.send(Method::GET, &prompt, token, None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["content"], "Summarize {{doc}}");
+
+    let body = json!({ "title": "Summary", "content": "Summarize {{doc}} briefly" });
+    let reply = app.send(Method::PUT, &prompt, token, Some(body)).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["version"], 2);
+    assert_eq!(reply.body["tags"], json!(["a", "b"]));
+
+    let reply = app.send(Method::GET, &format!("{prompt}/versions"), token, None).await;
+    let versions: Vec<_> = reply.body.as_array().unwrap().iter().map(|v| v["version"].clone()).collect();
+    assert_eq!(versions, [json!(1), json!(2)]);
+    let reply = app.send(Method::GET, &format!("{prompt}/versions/1"), token, None).await;
+    assert_eq!(reply.body["content"], "Summarize {{doc}}");
+    let reply = app.send(Method::GET, &format!("{prompt}/versions/3"), token, None).await;
+    assert_eq!(reply.code(), "prompt_version_not_found");
+
+    let reply = app.send(Method::POST, &format!("{prompt}/render"), token, Some(json!({ "doc": "this" }))).await;
+    assert_eq!(reply.body["text"], "Summarize this briefly");
+
+    let reply = app.send(Method::GET, &format!("{prompts}?tag=a"), token, None).await;
+    assert_eq!(reply.body["items"].as_array().unwrap().len(), 1);
+
+    let reply = app.send(Method::DELETE, &prompt, token, None).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    let reply = app.send(Method::GET, &prompt, token, None).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+    let reply = app.send(Method::GET, &format!("/workspaces/{ws}/trash"), token, None).await;
+    assert_eq!(reply.body[0]["id"], id.as_str());
+
+    let reply = app.send(Method::POST, &format!("{prompt}/restore"), token, None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    let reply = app.send(Method::GET, &prompt, token, None).await;
+    assert_eq!(reply.body["version"], 2);
+}
+
+// Ids are UUIDs and timestamps keep microseconds, as Postgres stores them
+async fn ids_and_timestamps_round_trip(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let token = Some(token.as_str());
+
+    let reply = app.send(Method::POST, &prompts, token, Some(json!({ "title": "Greeting", "content": "Hello" }))).await;
+    let id = reply.body["id"].as_str().unwrap().to_string();
+    assert!(Uuid::parse_str(&id).is_ok(), "{id}");
+    let created_at = reply.body["created_at"].clone();
+    assert_eq!(reply.body["updated_at"], created_at);
+    let at: DateTime<Utc> = serde_json::from_value(created_at.clone()).unwrap();
+    assert_eq!(at.timestamp_subsec_nanos() % 1000, 0);
+    let prompt = format!("{prompts}/{id}");
+
+    let reply = app.send(Method::GET, &prompt, token, None).await;
+    assert_eq!(reply.body["created_at"], created_at);
+    let reply = app.send(Method::GET, &format!("{prompt}/versions"), token, None).await;
+    assert_eq!(reply.body[0]["created_at"], created_at);
+
+    let reply = app.send(Method::PUT, &prompt, token, Some(json!({ "title": "Greeting", "content": "Hi" }))).await;
+    assert_eq!(reply.body["created_at"], created_at);
+    let updated_at: DateTime<Utc> = serde_json::from_value(reply.body["updated_at"].clone()).unwrap();
+    assert!(updated_at >= at);
+
+    // The exact timestamp works as a filter bound both ways
+    let bound = created_at.as_str().unwrap();
+    let reply = app.send(Method::GET, &format!("{prompts}?created_after={bound}"), token, None).await;
+    assert_eq!(reply.body["items"], json!([]));
+    let reply = app.send(Method::GET, &format!("{prompts}?created_before={bound}"), token, None).await;
+    assert_eq!(reply.body["items"], json!([]));
+}
+
+async fn cursors_page_through_every_prompt_once(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let token = Some(token.as_str());
+    // Repeated titles leave the id to break ties
+    for title in ["Delta", "Alpha", "Echo", "Alpha", "Charlie", "Bravo", "Alpha"] {
+        let reply = app.send(Method::POST, &prompts, token, Some(json!({ "title": title, "content": "Hi" }))).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+    }
+    let ids = |reply: &Reply| -> Vec<String> {
+        reply.body["items"].as_array().unwrap().iter().map(|p| p["id"].as_str().unwrap().to_string()).collect()
+    };
+
+    for query in ["sort=created_at", "sort=created_at&order=desc", "sort=title", "sort=title&order=desc"] {
+        let everything = ids(&app.send(Method::GET, &format!("{prompts}?{query}"), token, None).await);
+        assert_eq!(everything.len(), 7);
+
+        let mut paged = Vec::new();
+        let mut next = format!("{prompts}?{query}&limit=3");
+        loop {
+            let reply = app.send(Method::GET, &next, token, None).await;
+            assert_eq!(reply.status, StatusCode::OK);
+            let page = ids(&reply);
+            assert!(page.len() <= 3);
+            paged.extend(page);
+            let Some(cursor) = reply.body["next_cursor"].as_str() else {
+                break;
+            };
+            next = format!("{prompts}?{query}&limit=3&cursor={cursor}");
+        }
+        assert_eq!(paged, everything, "{query}");
+    }
+
+    // A full last page still says there is nothing after it
+    let reply = app.send(Method::GET, &format!("{prompts}?limit=7"), token, None).await;
+    assert_eq!(reply.body["next_cursor"], Value::Null);
+
+    let reply = app.send(Method::GET, &format!("{prompts}?sort=title&limit=3"), token, None).await;
+    let cursor = reply.body["next_cursor"].as_str().unwrap().to_string();
+    let mut tampered = cursor.clone();
+    tampered.insert(4, '!');
+    for uri in [
+        format!("{prompts}?sort=title&cursor={tampered}"),
+        format!("{prompts}?sort=title&cursor={}", &cursor[..cursor.len() - 5]),
+        // A title cursor doesn't fit a created_at listing
+        format!("{prompts}?sort=created_at&cursor={cursor}"),
+    ] {
+        let reply = app.send(Method::GET, &uri, token, None).await;
+        assert_eq!(reply.status, StatusCode::BAD_REQUEST, "{uri}");
+        assert_eq!(reply.code(), "invalid_cursor");
+    }
+}
+
+async fn only_title_and_content_changes_mint_versions(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let token = Some(token.as_str());
+    let body = json!({ "title": "Greeting", "content": "Hello", "tags": ["a"] });
+    let reply = app.send(Method::POST, &format!("/workspaces/{ws}/prompts"), token, Some(body)).await;
+    let prompt = format!("/workspaces/{ws}/prompts/{}", reply.body["id"].as_str().unwrap());
+    let revision = reply.body["revision"].as_i64().unwrap();
+
+    let body = json!({ "title": "Greeting", "content": "Hello", "tags": ["a", "b"] });
+    let reply = app.send(Method::PUT, &prompt, token, Some(body)).await;
+    assert_eq!((reply.body["version"].clone(), reply.body["tags"].clone()), (json!(1), json!(["a", "b"])));
+    assert_eq!(reply.body["revision"], revision + 1);
+
+    let body = json!({ "slug": "hello", "title": "Greeting", "content": "Hello" });
+    let reply = app.send(Method::PUT, &prompt, token, Some(body)).await;
+    assert_eq!((reply.body["version"].clone(), reply.body["slug"].clone()), (json!(1), json!("hello")));
+    assert_eq!(reply.body["revision"], revision + 2);
+
+    let reply = app.send(Method::PUT, &prompt, token, Some(json!({ "title": "Greeting", "content": "Hi" }))).await;
+    assert_eq!(reply.body["version"], 2);
+    assert_eq!(reply.body["revision"], revision + 3);
+
+    let reply = app.send(Method::GET, &format!("{prompt}/versions"), token, None).await;
+    let versions: Vec<_> = reply.body.as_array().unwrap().iter().map(|v| v["version"].clone()).collect();
+    assert_eq!(versions, [json!(1), json!(2)]);
+}
+
+async fn search_ranks_and_highlights_matches(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let token = Some(token.as_str());
+    for (title, content) in [
+        ("Summarize article", "Summarize {{text}} in three bullet points"),
+        ("Translate", "Translate {{text}} into French"),
+        ("Review", "List the risks in this summary"),
+    ] {
+        let reply = app.send(Method::POST, &prompts, token, Some(json!({ "title": title, "content": content }))).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+    }
+
+    let reply = app.send(Method::GET, &format!("{prompts}/search?q=summar*"), token, None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    let titles: Vec<_> = reply.body.as_array().unwrap().iter().map(|hit| hit["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, ["Summarize article", "Review"]);
+    assert_eq!(reply.body[0]["title_highlight"], "<mark>Summarize</mark> article");
+    assert!(reply.body[1]["snippet"].as_str().unwrap().contains("<mark>summary</mark>"), "{}", reply.body);
+
+    let reply = app.send(Method::GET, &format!("{prompts}/search?q=%22bullet%20points%22"), token, None).await;
+    assert_eq!(reply.body.as_array().unwrap().len(), 1);
+
+    // Trashed prompts drop out of the results
+    let reply = app.send(Method::GET, &format!("{prompts}?sort=title"), token, None).await;
+    let review = reply.body["items"][0]["id"].as_str().unwrap().to_string();
+    app.send(Method::DELETE, &format!("{prompts}/{review}"), token, None).await;
+    let reply = app.send(Method::GET, &format!("{prompts}/search?q=summar*"), token, None).await;
+    assert_eq!(reply.body.as_array().unwrap().len(), 1);
+}
+
+async fn search_highlights_escape_prompt_text(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let body = json!({ "title": "<b>Alert</b> & co", "content": "Say <script>alert('hi')</script> to \"them\"" });
+    app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+
+    let reply = app.send(Method::GET, &format!("{prompts}/search?q=alert"), Some(&token), None).await;
+    assert_eq!(reply.body[0]["title_highlight"], "&lt;b&gt;<mark>Alert</mark>&lt;/b&gt; &amp; co");
+    let snippet = reply.body[0]["snippet"].as_str().unwrap();
+    assert!(snippet.contains("&lt;script&gt;<mark>alert</mark>(&#39;hi&#39;)&lt;/script&gt;"), "{snippet}");
+    assert!(!snippet.contains("<script>"), "{snippet}");
+    // The prompt itself is returned as written
+    assert_eq!(reply.body[0]["title"], "<b>Alert</b> & co");
+}
+
+async fn slugs_find_prompts_and_old_slugs_redirect(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let by_slug = |slug: &str| format!("{prompts}/by-slug/{slug}");
+    let token = Some(token.as_str());
+
+    let body = json!({ "title": "Weekly Summary", "content": "Summarize the week" });
+    let reply = app.send(Method::POST, &prompts, token, Some(body.clone())).await;
+    assert_eq!(reply.body["slug"], "weekly-summary");
+    let id = reply.body["id"].clone();
+    let reply = app.send(Method::POST, &prompts, token, Some(body)).await;
+    assert_eq!(reply.body["slug"], "weekly-summary-2");
+
+    let reply = app.send(Method::GET, &by_slug("weekly-summary"), token, None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["id"], id);
+    let reply = app.send(Method::GET, &by_slug("monthly-summary"), token, None).await;
+    assert_eq!(reply.code(), "prompt_not_found");
+
+    let body = json!({ "slug": "Weekly Summary", "title": "Weekly Summary", "content": "Summarize the week" });
+    let reply = app.send(Method::POST, &prompts, token, Some(body)).await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body = json!({ "slug": "weekly-summary-2", "title": "Weekly Summary", "content": "Summarize the week" });
+    let reply = app.send(Method::POST, &prompts, token, Some(body)).await;
+    assert_eq!(reply.status, StatusCode::CONFLICT);
+
+    // A renamed prompt keeps answering to its old slug, query string and all
+    let body = json!({ "slug": "summary", "title": "Weekly Summary", "content": "Summarize the week" });
+    let reply = app.send(Method::PUT, &format!("{prompts}/{}", id.as_str().unwrap()), token, Some(body)).await;
+    assert_eq!(reply.body["slug"], "summary");
+    let reply = app.send(Method::GET, &format!("{}?label=production", by_slug("weekly-summary")), token, None).await;
+    assert_eq!(reply.status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(reply.headers[header::LOCATION], format!("{}?label=production", by_slug("summary")));
+    let reply = app.send(Method::GET, &by_slug("summary"), token, None).await;
+    assert_eq!(reply.body["id"], id);
+
+    // Deriving a slug skips old ones, but a prompt may claim one explicitly
+    let body = json!({ "title": "Weekly summary", "content": "Summarize the week again" });
+    let reply = app.send(Method::POST, &prompts, token, Some(body)).await;
+    assert_eq!(reply.body["slug"], "weekly-summary-3");
+    let body = json!({ "slug": "weekly-summary", "title": "Weekly", "content": "Week" });
+    let reply = app.send(Method::POST, &prompts, token, Some(body)).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    let reply = app.send(Method::GET, &by_slug("weekly-summary"), token, None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["title"], "Weekly");
+
+    // Trashed prompts can't be looked up
+    app.send(Method::DELETE, &format!("{prompts}/{}", id.as_str().unwrap()), token, None).await;
+    let reply = app.send(Method::GET, &by_slug("summary"), token, None).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+}
+
+async fn etags_guard_concurrent_writes(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let body = json!({ "title": "Greeting", "content": "Hello" });
+    let reply = app.send(Method::POST, &format!("/workspaces/{ws}/prompts"), Some(&token), Some(body)).await;
+    let prompt = format!("/workspaces/{ws}/prompts/{}", reply.body["id"].as_str().unwrap());
+    let etag = reply.headers[header::ETAG].to_str().unwrap().to_string();
+
+    let get = |tag: &str| {
+        Request::get(&prompt)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .header(header::IF_NONE_MATCH, tag)
+            .body(Body::empty())
+            .unwrap()
+    };
+    assert_eq!(app.request(get(&etag)).await.status, StatusCode::NOT_MODIFIED);
+    assert_eq!(app.request(get("\"0\"")).await.status, StatusCode::OK);
+
+    let patch = |tag: &str, content: &str| {
+        Request::patch(&prompt)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .header(header::CONTENT_TYPE, "application/merge-patch+json")
+            .header(header::IF_MATCH, tag)
+            .body(Body::from(json!({ "content": content }).to_string()))
+            .unwrap()
+    };
+    let reply = app.request(patch(&etag, "Hi")).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["title"], "Greeting");
+    assert_eq!(reply.body["content"], "Hi");
+
+    // The same tag is now stale
+    let reply = app.request(patch(&etag, "Hey")).await;
+    assert_eq!(reply.status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(reply.code(), "precondition_failed");
+}
+
+async fn etags_tell_prompts_apart_when_a_slug_moves(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let by_slug = format!("{prompts}/by-slug/greeting");
+    let with_tag = |request: axum::http::request::Builder, name, tag: &str| {
+        request.header(header::AUTHORIZATION, format!("Bearer {token}")).header(name, tag)
+    };
+
+    let body = json!({ "slug": "greeting", "title": "Greeting", "content": "Hello" });
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+    let first = format!("{prompts}/{}", reply.body["id"].as_str().unwrap());
+    let cached = reply.headers[header::ETAG].to_str().unwrap().to_string();
+    let get = with_tag(Request::get(&by_slug), header::IF_NONE_MATCH, &cached).body(Body::empty()).unwrap();
+    assert_eq!(app.request(get).await.status, StatusCode::NOT_MODIFIED);
+
+    // The slug moves to a new prompt, which is at the same revision the
+    // cached tag was issued for
+    let body = json!({ "slug": "old-greeting", "title": "Greeting", "content": "Hello" });
+    app.send(Method::PUT, &first, Some(&token), Some(body)).await;
+    let body = json!({ "slug": "greeting", "title": "Welcome", "content": "Welcome" });
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    assert_eq!(reply.body["revision"], 1);
+    let second = format!("{prompts}/{}", reply.body["id"].as_str().unwrap());
+
+    let get = with_tag(Request::get(&by_slug), header::IF_NONE_MATCH, &cached).body(Body::empty()).unwrap();
+    let reply = app.request(get).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["title"], "Welcome");
+
+    let body = Body::from(json!({ "title": "Welcome", "content": "Hi" }).to_string());
+    let put = with_tag(Request::put(&second), header::IF_MATCH, &cached)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap();
+    assert_eq!(app.request(put).await.status, StatusCode::PRECONDITION_FAILED);
+}
+
+async fn labels_pin_versions_and_record_moves(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let body = json!({ "title": "Greeting", "content": "Hello" });
+    let reply = app.send(Method::POST, &format!("/workspaces/{ws}/prompts"), Some(&token), Some(body)).await;
+    let id = reply.body["id"].as_str().unwrap().parse::<Uuid>().unwrap();
+    let prompt = format!("/workspaces/{ws}/prompts/{id}");
+    for content in ["Hello {{name}}", "Hi {{name}}"] {
+        let body = json!({ "title": "Greeting", "content": content });
+        app.send(Method::PUT, &prompt, Some(&token), Some(body)).await;
+    }
+    let production = format!("{prompt}/labels/production");
+    let read = |tag: Option<&str>| {
+        let mut request = Request::get(format!("{prompt}?label=production"))
+            .header(header::AUTHORIZATION, format!("Bearer {token}"));
+        if let Some(tag) = tag {
+            request = request.header(header::IF_NONE_MATCH, tag);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let reply = app.send(Method::PUT, &production, Some(&token), Some(json!({ "version": 1 }))).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["name"], "production");
+    assert_eq!(reply.body["version"], 1);
+
+    // The labelled version's text, tagged with the label's version too
+    let reply = app.request(read(None)).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["content"], "Hello");
+    assert_eq!(reply.body["version"], 1);
+    assert_eq!(reply.body["variables"], json!([]));
+    let etag = reply.headers[header::ETAG].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}-3.1\"", id.simple()));
+    assert_eq!(app.request(read(Some(&etag))).await.status, StatusCode::NOT_MODIFIED);
+
+    let reply = app.send(Method::PUT, &production, Some(&token), Some(json!({ "version": 2 }))).await;
+    assert_eq!(reply.body["version"], 2);
+    let reply = app.request(read(Some(&etag))).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["content"], "Hello {{name}}");
+    assert_eq!(reply.headers[header::ETAG], format!("\"{}-3.2\"", id.simple()).as_str());
+
+    // Plain reads still get the latest version
+    let reply = app.send(Method::GET, &prompt, Some(&token), None).await;
+    assert_eq!(reply.body["content"], "Hi {{name}}");
+
+    app.send(Method::PUT, &format!("{prompt}/labels/beta"), Some(&token), Some(json!({ "version": 3 }))).await;
+    let reply = app.send(Method::GET, &format!("{prompt}/labels"), Some(&token), None).await;
+    let labels: Vec<_> = reply.body.as_array().unwrap().iter().map(|l| (l["name"].clone(), l["version"].clone())).collect();
+    assert_eq!(labels, [(json!("beta"), json!(3)), (json!("production"), json!(2))]);
+
+    let reply = app.send(Method::GET, &format!("{production}/history"), Some(&token), None).await;
+    let moves: Vec<_> = reply
+        .body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["from_version"].clone(), m["to_version"].clone(), m["moved_by_email"].clone()))
+        .collect();
+    assert_eq!(
+        moves,
+        [(Value::Null, json!(1), json!("ada@example.com")), (json!(1), json!(2), json!("ada@example.com"))]
+    );
+
+    let reply = app.send(Method::GET, &format!("{prompt}?label=nope"), Some(&token), None).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+    assert_eq!(reply.code(), "label_not_found");
+    let reply = app.send(Method::GET, &format!("{prompt}/labels/nope"), Some(&token), None).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+    assert_eq!(reply.code(), "label_not_found");
+    let missing = format!("/workspaces/{ws}/prompts/{}/labels/production/history", Uuid::new_v4());
+    let reply = app.send(Method::GET, &missing, Some(&token), None).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+    assert_eq!(reply.code(), "prompt_not_found");
+
+    let reply = app.send(Method::PUT, &production, Some(&token), Some(json!({ "version": 4 }))).await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(reply.body["errors"][0]["field"], "version");
+    assert_eq!(reply.body["errors"][0]["code"], "unknown_version");
+    let reply = app.send(Method::PUT, &format!("{prompt}/labels/Prod!"), Some(&token), Some(json!({ "version": 1 }))).await;
+    assert_eq!(reply.status, StatusCode::BAD_REQUEST);
+    assert_eq!(reply.code(), "invalid_label");
+
+    // Failed moves leave the label and its history alone
+    let reply = app.send(Method::GET, &format!("{production}/history"), Some(&token), None).await;
+    assert_eq!(reply.body.as_array().unwrap().len(), 2);
+}
+
+async fn tags_are_normalized_and_counted(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let other = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let bearer = format!("Bearer {token}");
+    let token = Some(token.as_str());
+    let create = |title: &str, tags: Value| json!({ "title": title, "content": "Hi", "tags": tags });
+
+    let reply = app.send(Method::POST, &prompts, token, Some(create("One", json!([" News ", "news", "Draft"])))).await;
+    assert_eq!(reply.body["tags"], json!(["draft", "news"]));
+    let one = format!("{prompts}/{}", reply.body["id"].as_str().unwrap());
+    let reply = app.send(Method::POST, &prompts, token, Some(create("Two", json!(["NEWS", "sport"])))).await;
+    let two = format!("{prompts}/{}", reply.body["id"].as_str().unwrap());
+    app.send(Method::POST, &prompts, token, Some(create("Three", json!(["sport"])))).await;
+    app.send(Method::POST, &format!("/workspaces/{other}/prompts"), token, Some(create("Elsewhere", json!(["news"]))))
+        .await;
+
+    // Updates and patches normalize too
+    let reply = app.send(Method::PUT, &one, token, Some(json!({ "title": "One", "content": "Hi", "tags": ["Draft ", "SPORT"] }))).await;
+    assert_eq!(reply.body["tags"], json!(["draft", "sport"]));
+    let patch = Request::patch(&two)
+        .header(header::AUTHORIZATION, bearer)
+        .header(header::CONTENT_TYPE, "application/merge-patch+json")
+        .body(Body::from(json!({ "tags": ["Sport", "News", "sport"] }).to_string()))
+        .unwrap();
+    assert_eq!(app.request(patch).await.body["tags"], json!(["news", "sport"]));
+
+    let counts = |reply: Reply| -> Vec<(String, i64)> {
+        reply
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tag| (tag["name"].as_str().unwrap().to_string(), tag["count"].as_i64().unwrap()))
+            .collect()
+    };
+    let tags = format!("/workspaces/{ws}/tags");
+    let reply = app.send(Method::GET, &tags, token, None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(counts(reply), [("draft".to_string(), 1), ("news".to_string(), 1), ("sport".to_string(), 3)]);
+
+    // Filters are normalized like the tags they match
+    let reply = app.send(Method::GET, &format!("{prompts}?tag=%20SPORT%20&tag=News&match=all"), token, None).await;
+    assert_eq!(reply.body["items"].as_array().unwrap().len(), 1);
+    assert_eq!(reply.body["items"][0]["title"], "Two");
+
+    // Trashed prompts drop out of the counts
+    app.send(Method::DELETE, &two, token, None).await;
+    let reply = app.send(Method::GET, &tags, token, None).await;
+    assert_eq!(counts(reply), [("draft".to_string(), 1), ("sport".to_string(), 2)]);
+}
+
+async fn metrics_count_requests_by_route(app: TestApp) {
+    let _recorder = metrics::set_default_local_recorder(&app.recorder);
+    telemetry::describe_metrics();
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let mut ids = Vec::new();
+    for title in ["One", "Two"] {
+        let body = json!({ "title": title, "content": "Hello" });
+        let reply = app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+        ids.push(reply.body["id"].as_str().unwrap().to_string());
+    }
+    app.send(Method::DELETE, &format!("{prompts}/{}", ids[1]), Some(&token), None).await;
+    app.send(Method::GET, "/no/such/path", None, None).await;
+
+    let reply = app.send(Method::GET, "/metrics", None, None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.headers[header::CONTENT_TYPE], "text/plain; version=0.0.4; charset=utf-8");
+    let text = reply.body.as_str().unwrap();
+    let has = |line: &str| text.lines().any(|l| l == line);
+
+    // Routes, not paths, so ids don't each get a series
+    assert!(has(r#"http_requests_total{method="POST",route="/workspaces/:ws/prompts",status="201"} 2"#));
+    assert!(has(r#"http_requests_total{method="DELETE",route="/workspaces/:ws/prompts/:id",status="204"} 1"#));
+    assert!(has(r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#));
+    assert!(has(r#"http_request_duration_seconds_count{method="POST",route="/workspaces/:ws/prompts"} 2"#));
+    assert!(text.contains(r#"http_request_duration_seconds_bucket{method="POST",route="/workspaces/:ws/prompts",le="0.005"}"#));
+    assert!(ids.iter().all(|id| !text.contains(id.as_str())));
+
+    assert!(has(r#"prompthub_prompts{state="live"} 1"#));
+    assert!(has(r#"prompthub_prompts{state="trashed"} 1"#));
+    assert!(has("db_pool_acquire_timeouts_total 0"));
+    if let Some(pool) = app.state.schema.pool_stats() {
+        let gauge = |name: &str| {
+            let line = text.lines().find(|l| l.starts_with(name)).unwrap();
+            line[name.len()..].trim().parse::<u32>().unwrap()
+        };
+        let open = gauge(r#"db_pool_connections{state="idle"}"#) + gauge(r#"db_pool_connections{state="in_use"}"#);
+        assert!(open <= pool.max);
+        assert_eq!(gauge("db_pool_max_connections "), pool.max);
+    }
+
+    // Prompts are counted at most once a minute, whatever the scrape rate
+    let body = json!({ "title": "Three", "content": "Hello" });
+    app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+    let reply = app.send(Method::GET, "/metrics", None, None).await;
+    let text = reply.body.as_str().unwrap();
+    assert!(text.lines().any(|l| l == r#"prompthub_prompts{state="live"} 1"#));
+}
+
+async fn schema_status_is_for_operators_only(app: TestApp) {
+    let reply = app.send(Method::GET, "/admin/schema", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert!(reply.body.is_object());
+
+    // A session or an API key isn't enough, even for a workspace owner
+    let (_, token) = app.user("ada@example.com").await;
+    app.workspace(&token).await;
+    let reply = app
+        .send(Method::POST, "/api-keys", Some(&token), Some(json!({ "name": "ci", "scopes": ["prompts:read"] })))
+        .await;
+    let key = reply.body["key"].as_str().unwrap().to_string();
+    for token in [None, Some(token.as_str()), Some(key.as_str()), Some(&ADMIN_TOKEN[1..])] {
+        let reply = app.send(Method::GET, "/admin/schema", token, None).await;
+        assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+    }
+
+    // Without a configured token nobody gets in
+    let state = AppState { admin_token: None, ..app.state.clone() };
+    let disabled = TestApp { router: super::app(state.clone(), &ServerConfig::default()), state, recorder: app.recorder };
+    let reply = disabled.send(Method::GET, "/admin/schema", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+    assert_eq!(reply.code(), "admin_disabled");
+}
+
+async fn invalid_bodies_are_problem_details(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(json!({ "title": " ", "content": "a\u{0}b" }))).await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(reply.code(), "validation_failed");
+    let fields: Vec<_> = reply.body["errors"].as_array().unwrap().iter().map(|e| e["field"].clone()).collect();
+    assert!(fields.contains(&json!("title")) && fields.contains(&json!("content")));
+
+    let body = json!({ "title": "t".repeat(validation::MAX_TITLE_CHARS + 1), "content": "c".repeat(validation::MAX_CONTENT_BYTES + 1) });
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(reply.body["errors"][0]["field"], "content");
+    assert_eq!(reply.body["errors"][0]["message"], format!("must be at most {} bytes", validation::MAX_CONTENT_BYTES));
+    assert_eq!(reply.body["errors"][1]["field"], "title");
+    assert_eq!(reply.body["errors"][1]["message"], format!("must be between 1 and {} characters", validation::MAX_TITLE_CHARS));
+
+    let request = Request::post(&prompts)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{\"title\":"))
+        .unwrap();
+    let reply = app.request(request).await;
+    assert_eq!(reply.status, StatusCode::BAD_REQUEST);
+    assert_eq!(reply.code(), "malformed_json");
+}
+
+async fn bad_paths_and_queries_are_problem_details(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let body = json!({ "title": "Greeting", "content": "Hello" });
+    let reply = app.send(Method::POST, &format!("/workspaces/{ws}/prompts"), Some(&token), Some(body)).await;
+    let prompt = format!("/workspaces/{ws}/prompts/{}", reply.body["id"].as_str().unwrap());
+
+    for (uri, code) in [
+        (format!("/workspaces/{ws}/prompts/not-a-uuid"), "invalid_path"),
+        (format!("{prompt}/versions/latest"), "invalid_path"),
+        ("/api-keys/42".to_string(), "invalid_path"),
+        (format!("/workspaces/{ws}/prompts?sort=bogus"), "invalid_query"),
+        (format!("/workspaces/{ws}/prompts?limit=ten"), "invalid_query"),
+        (format!("{prompt}/diff?from=1"), "invalid_query"),
+        (format!("/workspaces/{ws}/prompts/search"), "invalid_query"),
+    ] {
+        let method = if uri.starts_with("/api-keys") { Method::DELETE } else { Method::GET };
+        let reply = app.send(method, &uri, Some(&token), None).await;
+        assert_eq!(reply.status, StatusCode::BAD_REQUEST, "{uri}");
+        assert_eq!(reply.headers[header::CONTENT_TYPE], "application/problem+json", "{uri}");
+        assert_eq!(reply.code(), code, "{uri}");
+        assert!(reply.body["request_id"].is_string(), "{uri}");
+    }
+}
+
+async fn tags_are_validated_on_every_write(app: TestApp) {
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let prompts = format!("/workspaces/{ws}/prompts");
+    let errors = |reply: &Reply| -> Vec<(String, String)> {
+        assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+        reply.body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["field"].as_str().unwrap().to_string(), e["code"].as_str().unwrap().to_string()))
+            .collect()
+    };
+    let field = |name: &str, code: &str| vec![(name.to_string(), code.to_string())];
+
+    let body = json!({ "title": "T", "content": "C", "tags": ["ok", "a\u{0}b", "x".repeat(5000)] });
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+    assert_eq!(errors(&reply), field("tags[1]", "control_characters"));
+
+    let body = json!({ "title": "T", "content": "C", "tags": ["ok", "x".repeat(65)] });
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+    assert_eq!(errors(&reply), field("tags[1]", "length"));
+
+    let tags: Vec<_> = (0..21).map(|n| format!("tag-{n}")).collect();
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(json!({ "title": "T", "content": "C", "tags": tags }))).await;
+    assert_eq!(errors(&reply), field("tags", "too_many"));
+
+    let body = json!({ "title": "T", "content": "C", "tags": ["x".repeat(64)] });
+    let reply = app.send(Method::POST, &prompts, Some(&token), Some(body)).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    let prompt = format!("{prompts}/{}", reply.body["id"].as_str().unwrap());
+
+    let reply = app.send(Method::PUT, &prompt, Some(&token), Some(json!({ "title": "T", "content": "C", "tags": ["  "] }))).await;
+    assert_eq!(errors(&reply), field("tags[0]", "blank"));
+
+    let request = Request::patch(&prompt)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::CONTENT_TYPE, "application/merge-patch+json")
+        .body(Body::from(json!({ "tags": ["fine", "tab\there"] }).to_string()))
+        .unwrap();
+    assert_eq!(errors(&app.request(request).await), field("tags[1]", "control_characters"));
+}
+
+// Log output kept in memory, one JSON object per line
+#[derive(Clone, Default)]
+struct Logs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Logs {
+    fn lines(&self) -> Vec<Value> {
+        let logs = self.0.lock().unwrap();
+        logs.split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn request_ids_are_echoed_or_generated_and_logged() {
+    let logs = Logs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _subscriber = tracing::subscriber::set_default(subscriber);
+    let app = TestApp::new("memory://").await;
+    let get = |id: Option<&str>| {
+        let mut request = Request::get("/workspaces");
+        if let Some(id) = id {
+            request = request.header(&request_id::HEADER, id);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let reply = app.request(get(Some("lb-7f3a:42"))).await;
+    assert_eq!(reply.headers[&request_id::HEADER], "lb-7f3a:42");
+    assert_eq!(reply.body["request_id"], "lb-7f3a:42");
+
+    // Missing or unusable ids are replaced with a fresh one
+    let mut generated = Vec::new();
+    for id in [None, Some("two words"), Some(&*"x".repeat(129))] {
+        let reply = app.request(get(id)).await;
+        let id = reply.headers[&request_id::HEADER].to_str().unwrap().to_string();
+        assert!(Uuid::parse_str(&id).is_ok(), "{id}");
+        assert_eq!(reply.body["request_id"], id.as_str());
+        generated.push(id);
+    }
+    assert_ne!(generated[0], generated[1]);
+
+    // Every request's log line carries its id in the request span
+    let completed: Vec<_> = logs
+        .lines()
+        .into_iter()
+        .filter(|line| line["message"] == "request completed")
+        .collect();
+    let ids: Vec<_> = completed.iter().map(|line| line["span"]["request_id"].as_str().unwrap()).collect();
+    assert_eq!(ids[0], "lb-7f3a:42");
+    assert_eq!(ids[1..], generated);
+    assert_eq!(completed[0]["span"]["name"], "request");
+    assert_eq!(completed[0]["span"]["route"], "/workspaces");
+    assert_eq!(completed[0]["status"], 401);
+}
+
+// A bare OTLP/HTTP collector that keeps every body posted to `/v1/traces`.
+// It runs on a thread of its own because flushing the exporter blocks.
+struct Collector {
+    addr: std::net::SocketAddr,
+    traces: Arc<std::sync::Mutex<Vec<Value>>>,
+}
+
+impl Collector {
+    fn start() -> Self {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let traces = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let received = traces.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                if request_line.starts_with("POST /v1/traces ") {
+                    received.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}")
+                    .unwrap();
+            }
+        });
+        Collector { addr, traces }
+    }
+
+    // Every exported span, flattened out of its resource and scope
+    fn spans(&self) -> Vec<Value> {
+        let traces = self.traces.lock().unwrap();
+        traces
+            .iter()
+            .flat_map(|body| body["resourceSpans"].as_array().cloned().unwrap_or_default())
+            .flat_map(|resource| resource["scopeSpans"].as_array().cloned().unwrap_or_default())
+            .flat_map(|scope| scope["spans"].as_array().cloned().unwrap_or_default())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn requests_continue_the_callers_trace_over_otlp() {
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let collector = Collector::start();
+    let otel = config::OtelConfig {
+        endpoint: format!("http://{}", collector.addr),
+        protocol: config::OtlpProtocol::Json,
+        ..config::OtelConfig::default()
+    };
+    let provider = telemetry::tracer_provider(&otel).unwrap();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    // Scoped to this test's thread, which runs the whole request
+    let _subscriber = tracing::subscriber::set_default(subscriber);
+    opentelemetry::global::set_text_map_propagator(opentelemetry_sdk::propagation::TraceContextPropagator::new());
+
+    let app = TestApp::new("memory://").await;
+    let (_, token) = app.user("ada@example.com").await;
+    let ws = app.workspace(&token).await;
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let request = Request::get(format!("/workspaces/{ws}/prompts"))
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header("traceparent", format!("00-{trace_id}-00f067aa0ba902b7-01"))
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(app.request(request).await.status, StatusCode::OK);
+    provider.shutdown().unwrap();
+
+    let spans: Vec<_> = collector.spans().into_iter().filter(|span| span["traceId"] == trace_id).collect();
+    let request = spans.iter().find(|span| span["kind"] == 2).expect("a server span");
+    assert_eq!(request["name"], "GET /workspaces/:ws/prompts");
+    assert_eq!(request["parentSpanId"], "00f067aa0ba902b7");
+    let store_calls: Vec<_> = spans
+        .iter()
+        .filter(|span| span["kind"] == 3 && span["parentSpanId"] == request["spanId"])
+        .map(|span| span["name"].as_str().unwrap())
+        .collect();
+    assert!(store_calls.contains(&"list_prompts"), "{store_calls:?}");
+}
+
+// A request body that never arrives, holding its request open
+struct Stalled;
+
+impl http_body::Body for Stalled {
+    type Data = body::Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        std::task::Poll::Pending
+    }
+}
+
+fn stalled_register() -> Request<Body> {
+    Request::post("/auth/register")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::new(Stalled))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn oversize_bodies_are_turned_away_before_reading() {
+    let server = ServerConfig { body_limit_bytes: 16, ..ServerConfig::default() };
+    let app = TestApp::with_server("memory://", &server).await;
+
+    // Declared too large: rejected on the header, the body is never read
+    let request = Request::post("/auth/register")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_LENGTH, "17")
+        .body(Body::new(Stalled))
+        .unwrap();
+    let reply = app.request(request).await;
+    assert_eq!(reply.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(reply.code(), "payload_too_large");
+    assert_eq!(reply.body["detail"], "Request bodies are limited to 16 bytes");
+
+    // Undeclared but too large: caught while reading
+    let body = json!({ "email": "ada@example.com", "password": "correct horse" });
+    let reply = app.send(Method::POST, "/auth/register", None, Some(body)).await;
+    assert_eq!(reply.status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn requests_beyond_the_concurrency_limit_are_shed() {
+    let server = ServerConfig { max_concurrent_requests: 1, ..ServerConfig::default() };
+    let app = TestApp::with_server("memory://", &server).await;
+
+    // Polled once, the first request takes the only permit and waits on
+    // its body
+    let mut first = Box::pin(app.router.clone().oneshot(stalled_register()));
+    tokio::select! {
+        biased;
+        _ = &mut first => panic!("a stalled request completed"),
+        _ = std::future::ready(()) => {}
+    }
+
+    let reply = app.send(Method::GET, "/workspaces", None, None).await;
+    assert_eq!(reply.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(reply.code(), "overloaded");
+    // Probes are exempt, so a saturated instance isn't restarted
+    assert_eq!(app.send(Method::GET, "/healthz", None, None).await.status, StatusCode::OK);
+
+    // Its permit goes back when it is dropped
+    drop(first);
+    let reply = app.send(Method::GET, "/workspaces", None, None).await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn slow_requests_time_out() {
+    let server = ServerConfig { request_timeout_secs: 0, ..ServerConfig::default() };
+    let app = TestApp::with_server("memory://", &server).await;
+
+    let reply = app.request(stalled_register()).await;
+    assert_eq!(reply.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(reply.code(), "request_timeout");
+    assert!(reply.body["request_id"].is_string());
+}
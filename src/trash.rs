@@ -0,0 +1,26 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::config::TrashConfig;
+use crate::store::PromptStore;
+
+// Periodically deletes prompts that have sat in the trash for longer than
+// the retention period. Failures are logged and retried on the next tick.
+pub fn spawn_purge(prompts: Arc<dyn PromptStore>, config: TrashConfig) {
+    let retention = chrono::Duration::days(i64::from(config.retention_days));
+    let mut interval = tokio::time::interval(Duration::from_secs(config.purge_interval_secs));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            match prompts.purge_deleted(Utc::now() - retention).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "purged deleted prompts"),
+                Err(e) => tracing::warn!(error = %e, "purging deleted prompts failed"),
+            }
+        }
+    });
+}
@@ -0,0 +1,136 @@
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::FieldError;
+use crate::slug;
+
+pub const MAX_TITLE_CHARS: usize = 200;
+pub const MAX_CONTENT_BYTES: usize = 100 * 1024;
+pub const MAX_LABEL_CHARS: usize = 64;
+pub const MAX_WORKSPACE_NAME_CHARS: usize = 100;
+pub const MAX_TAGS: usize = 20;
+pub const MAX_TAG_CHARS: usize = 64;
+
+// Rules referenced from `#[validate(custom(...))]` attributes
+
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("blank").with_message("must not be blank".into()));
+    }
+    Ok(())
+}
+
+// Single-line text such as titles
+pub fn no_control_chars(value: &str) -> Result<(), ValidationError> {
+    if value.chars().any(char::is_control) {
+        return Err(ValidationError::new("control_characters")
+            .with_message("must not contain control characters".into()));
+    }
+    Ok(())
+}
+
+// Multi-line text may keep tabs and line breaks
+pub fn no_control_chars_except_whitespace(value: &str) -> Result<(), ValidationError> {
+    if value.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return Err(ValidationError::new("control_characters")
+            .with_message("must not contain control characters other than tabs and line breaks".into()));
+    }
+    Ok(())
+}
+
+pub fn title(value: &str) -> Result<(), ValidationError> {
+    char_count(value, 1, MAX_TITLE_CHARS)?;
+    not_blank(value)?;
+    no_control_chars(value)
+}
+
+pub fn content(value: &str) -> Result<(), ValidationError> {
+    content_size(value)?;
+    no_control_chars_except_whitespace(value)
+}
+
+pub fn workspace_name(value: &str) -> Result<(), ValidationError> {
+    char_count(value, 1, MAX_WORKSPACE_NAME_CHARS)?;
+    not_blank(value)?;
+    no_control_chars(value)
+}
+
+// Checked before tags are normalized, so a blank tag is an error rather than
+// silently dropped. The first bad tag is reported by its index.
+pub fn tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ValidationError::new("too_many")
+            .with_message(format!("must have at most {MAX_TAGS} tags").into()));
+    }
+    for (index, tag) in tags.iter().enumerate() {
+        let valid = not_blank(tag)
+            .and_then(|()| char_count(tag.trim(), 1, MAX_TAG_CHARS))
+            .and_then(|()| no_control_chars(tag));
+        if let Err(mut e) = valid {
+            e.add_param("index".into(), &index);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn char_count(value: &str, min: usize, max: usize) -> Result<(), ValidationError> {
+    if !(min..=max).contains(&value.chars().count()) {
+        return Err(ValidationError::new("length")
+            .with_message(format!("must be between {min} and {max} characters").into()));
+    }
+    Ok(())
+}
+
+pub fn slug(value: &str) -> Result<(), ValidationError> {
+    if !slug::is_valid(value) {
+        return Err(ValidationError::new("invalid_slug")
+            .with_message(format!("must be at most {} lowercase letters, digits and single dashes", slug::MAX_LEN).into()));
+    }
+    Ok(())
+}
+
+pub fn content_size(value: &str) -> Result<(), ValidationError> {
+    if value.len() > MAX_CONTENT_BYTES {
+        return Err(ValidationError::new("too_large")
+            .with_message(format!("must be at most {MAX_CONTENT_BYTES} bytes").into()));
+    }
+    Ok(())
+}
+
+// Label names such as `production` or `canary-2` appear in URLs, so they
+// stick to a small alphabet
+pub fn is_label(name: &str) -> bool {
+    (1..=MAX_LABEL_CHARS).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'))
+}
+
+// Flattens validator's per-field map into a stable, sorted list
+pub fn field_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut fields: Vec<FieldError> = errors
+        .errors()
+        .iter()
+        .filter_map(|(field, kind)| match kind {
+            ValidationErrorsKind::Field(errors) => Some((field, errors)),
+            _ => None,
+        })
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |e| FieldError {
+                // Rules over lists point at the offending item, as `tags[2]`
+                field: Some(match e.params.get("index") {
+                    Some(index) => format!("{field}[{index}]"),
+                    None => field.to_string(),
+                }),
+                code: e.code.to_string(),
+                message: e
+                    .message
+                    .as_ref()
+                    .map(|m| m.to_string())
+                    .unwrap_or_else(|| format!("failed the {} rule", e.code)),
+            })
+        })
+        .collect();
+    fields.sort_by(|a, b| a.field.cmp(&b.field));
+    fields
+}
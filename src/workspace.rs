@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::{AuthError, Authorized, RequiredScope, Scope};
+use crate::error::{ApiError, Resource};
+use crate::AppState;
+
+// Declared from least to most privileged so roles can be compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    // The least privileged role allowed to exercise a scope inside a workspace
+    pub fn required_for(scope: Scope) -> Role {
+        match scope {
+            Scope::PromptsRead => Role::Viewer,
+            Scope::PromptsWrite => Role::Editor,
+            Scope::PromptsDelete => Role::Owner,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AccessError {
+    Auth(AuthError),
+    BadPath,
+    NotFound,
+    Forbidden(Role),
+}
+
+impl From<AccessError> for ApiError {
+    fn from(e: AccessError) -> Self {
+        match e {
+            AccessError::Auth(e) => e.into(),
+            AccessError::BadPath => ApiError::bad_request("invalid_workspace_id", "Invalid workspace id"),
+            // Non-members can't tell a private workspace from a missing one
+            AccessError::NotFound => ApiError::NotFound(Resource::Workspace),
+            AccessError::Forbidden(role) => ApiError::forbidden(
+                "insufficient_role",
+                format!("Requires the {} role in this workspace", role.as_str()),
+            ),
+        }
+    }
+}
+
+impl IntoResponse for AccessError {
+    fn into_response(self) -> Response {
+        ApiError::from(self).into_response()
+    }
+}
+
+// A caller authorized for scope `S` who is a member of the `:ws` workspace
+// with a role high enough to exercise that scope.
+#[derive(Debug)]
+pub struct WorkspaceMember<S> {
+    pub user_id: Uuid,
+    pub workspace_id: Uuid,
+    pub role: Role,
+    _scope: PhantomData<S>,
+}
+
+#[async_trait]
+impl<S: RequiredScope + Send> FromRequestParts<AppState> for WorkspaceMember<S> {
+    type Rejection = AccessError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let auth = Authorized::<S>::from_request_parts(parts, state)
+            .await
+            .map_err(AccessError::Auth)?;
+
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AccessError::BadPath)?;
+        let workspace_id = params
+            .get("ws")
+            .and_then(|ws| ws.parse::<Uuid>().ok())
+            .ok_or(AccessError::BadPath)?;
+
+        let role = state
+            .accounts
+            .member_role(workspace_id, auth.user_id)
+            .await
+            .map_err(|e| AccessError::Auth(AuthError::Internal(e)))?
+            .ok_or(AccessError::NotFound)?;
+
+        let required = Role::required_for(S::SCOPE);
+        if role < required {
+            return Err(AccessError::Forbidden(required));
+        }
+
+        Ok(WorkspaceMember {
+            user_id: auth.user_id,
+            workspace_id,
+            role,
+            _scope: PhantomData,
+        })
+    }
+}